@@ -1,7 +1,9 @@
 //! Generic abstraction for UI applications.
 
+pub use self::animation::{Animations, Easing, Lerp, Transition, Tween};
 pub use self::widget::{Context, Properties, Text, Textures, Widget, WidgetId, Widgets};
 
+use std::fmt::{self, Debug, Formatter};
 use std::time::{Duration, Instant};
 
 use anyhow::Error;
@@ -11,22 +13,48 @@ use sdl2::render::Canvas;
 use sdl2::video::Window;
 use sdl2::Sdl;
 
+use self::stack::StateStack;
+
 const TARGET_FRAME_RATE: u16 = 60;
 const MESSAGE_BOX_KIND: MessageBoxFlag = MessageBoxFlag::ERROR;
 
+mod animation;
+mod stack;
 mod widget;
 
 /// An action to take upon receiving an SDL event.
-#[derive(Clone, Copy, Debug)]
-pub enum Action {
+pub enum Action<W: Widget> {
     /// Continue to run the application.
     Continue,
     /// Adjust the window fullscreen state.
     Fullscreen(Fullscreen),
+    /// Pause the current state and push a new state on top of it, e.g. to open a details page or
+    /// a modal dialog.
+    Push(Box<dyn State<W>>),
+    /// Pop the current state off the stack and resume the state below it. If there is no state
+    /// left, the application shuts down.
+    Pop,
+    /// Replace the current state with a new state.
+    Switch(Box<dyn State<W>>),
     /// Shut down the application.
     Quit,
 }
 
+impl<W: Widget> Debug for Action<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Action::Continue => f.write_str("Continue"),
+            Action::Fullscreen(fullscreen) => {
+                f.debug_tuple("Fullscreen").field(&fullscreen).finish()
+            }
+            Action::Push(_) => f.write_str("Push(..)"),
+            Action::Pop => f.write_str("Pop"),
+            Action::Switch(_) => f.write_str("Switch(..)"),
+            Action::Quit => f.write_str("Quit"),
+        }
+    }
+}
+
 /// A list of window fullscreen state transitions.
 #[derive(Clone, Copy, Debug)]
 pub enum Fullscreen {
@@ -38,14 +66,58 @@ pub enum Fullscreen {
     Toggle,
 }
 
-/// A trait implemented by the main application state.
+/// A trait implemented by the application states, e.g. a menu or a details page.
+///
+/// States are kept on a stack, of which only the topmost state is active: it receives all events
+/// and updates. Every state has its own layer of widgets, which is only drawn while the state is
+/// active, or while all states above it are overlays.
 pub trait State<W: Widget> {
-    /// This method is called at initialization time, before any drawing has taken place, and is
-    /// responsible for building the initial UI widget layout of the application.
+    /// This method is called when the state is pushed onto the stack, before any drawing has
+    /// taken place, and is responsible for building the initial UI widget layout of the state.
+    ///
+    /// Widgets inserted as children of the root widget belong to this state, and are removed once
+    /// it is popped off the stack.
     ///
     /// This trait method is _required_ and is guaranteed to only be called once.
     fn initialize(&mut self, widgets: &mut Widgets<W>) -> anyhow::Result<()>;
 
+    /// This callback is called once on every frame while the state is active, after all pending
+    /// SDL events have been handled and before the widgets are updated and drawn.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method will do nothing.
+    fn update(&mut self, _widgets: &mut Widgets<W>) {}
+
+    /// This callback is called right after [`State::initialize()`], once the state has become
+    /// active.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method will do nothing.
+    fn on_enter(&mut self, _widgets: &mut Widgets<W>) {}
+
+    /// This callback is called when the state is popped off the stack or switched out, right
+    /// before its widgets are removed.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method will do nothing.
+    fn on_exit(&mut self, _widgets: &mut Widgets<W>) {}
+
+    /// This callback is called when another state is pushed on top of this one.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method will do nothing.
+    fn on_pause(&mut self, _widgets: &mut Widgets<W>) {}
+
+    /// This callback is called when this state becomes active again, after the state on top of it
+    /// was popped off the stack.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method will do nothing.
+    fn on_resume(&mut self, _widgets: &mut Widgets<W>) {}
+
+    /// Returns `true` if the states below this one should remain visible while it is active, e.g.
+    /// because it is a modal dialog which only covers part of the window.
+    ///
+    /// This trait method is _provided_. If it is not implemented, this method returns `false`.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
     /// This callback is called every time an [SDL event](sdl2::event::Event) is produced from the
     /// window event loop.
     ///
@@ -53,25 +125,24 @@ pub trait State<W: Widget> {
     ///
     /// This trait method is _provided_. If it is not implemented, this method will do nothing and
     /// always return [`Action::Continue`].
-    fn handle_event(&mut self, _event: &Event, _widgets: &mut Widgets<W>) -> Action {
+    fn handle_event(&mut self, _event: &Event, _widgets: &mut Widgets<W>) -> Action<W> {
         Action::Continue
     }
 }
 
-/// Engine which drives the application state and event loop.
-#[derive(Debug)]
-pub struct App<W, S> {
-    state: S,
+/// Engine which drives the application state stack and event loop.
+pub struct App<W: Widget> {
+    state: Box<dyn State<W>>,
     root_widget: W,
     error_message_box: Option<&'static str>,
 }
 
-impl<W: Widget, S: State<W>> App<W, S> {
-    /// Creates a new `App` with the given application [`State`] and root widget.
+impl<W: Widget> App<W> {
+    /// Creates a new `App` with the given initial application [`State`] and root widget.
     #[inline]
-    pub fn new(state: S, root_widget: W) -> Self {
+    pub fn new<S: State<W> + 'static>(state: S, root_widget: W) -> Self {
         App {
-            state,
+            state: Box::new(state),
             root_widget,
             error_message_box: None,
         }
@@ -94,7 +165,9 @@ impl<W: Widget, S: State<W>> App<W, S> {
     /// [`Window`](sdl2::video::Window) handle.
     ///
     /// Returns `Ok` when the application has exited successfully, or returns `Err` if the
-    /// application failed to initialize or an SDL error was encountered.
+    /// application failed to initialize or an SDL error was encountered. States pushed or switched
+    /// to later on which fail to initialize are reported on `stderr` instead, and the active state
+    /// keeps running.
     #[inline]
     pub fn run(self, sdl: Sdl, window: Window) -> anyhow::Result<()> {
         let mut canvas = window.into_canvas().accelerated().present_vsync().build()?;
@@ -110,30 +183,59 @@ impl<W: Widget, S: State<W>> App<W, S> {
         result
     }
 
-    fn main_loop(mut self, sdl: Sdl, canvas: &mut Canvas<Window>) -> anyhow::Result<()> {
+    fn main_loop(self, sdl: Sdl, canvas: &mut Canvas<Window>) -> anyhow::Result<()> {
         let mut events = sdl.event_pump().map_err(Error::msg)?;
 
+        let ttf_ctx = sdl2::ttf::init()?;
         let texture_creator = canvas.texture_creator();
-        let textures = Textures::new(&texture_creator)?;
+        let textures = Textures::new(&texture_creator, &ttf_ctx);
         let mut widgets = Widgets::new(self.root_widget, textures);
 
         // Build and populate the `Widgets` cache.
-        self.state.initialize(&mut widgets)?;
+        let mut states = StateStack::new();
+        states.push(self.state, &mut widgets)?;
+
+        let mut last_frame = Instant::now();
 
         'running: loop {
             let start = Instant::now();
+            let dt = start - last_frame;
+            last_frame = start;
 
             // Handle all pending SDL events.
             for event in events.poll_iter() {
-                match self.state.handle_event(&event, &mut widgets) {
+                let action = match states.top_mut() {
+                    Some(state) => state.handle_event(&event, &mut widgets),
+                    None => break 'running,
+                };
+
+                match action {
                     Action::Continue => {}
                     Action::Fullscreen(f) => fullscreen(f, canvas.window_mut(), &mut widgets)?,
+                    // States which fail to initialize are rolled back, leaving the active state
+                    // in place, so the application keeps running.
+                    Action::Push(state) => {
+                        if let Err(e) = states.push(state, &mut widgets) {
+                            eprintln!("error: could not open new state: {:?}", e);
+                        }
+                    }
+                    Action::Pop => states.pop(&mut widgets),
+                    Action::Switch(state) => {
+                        if let Err(e) = states.switch(state, &mut widgets) {
+                            eprintln!("error: could not switch to new state: {:?}", e);
+                        }
+                    }
                     Action::Quit => break 'running,
                 }
             }
 
-            // Advance the internal state of the widgets.
-            widgets.update();
+            // Advance the internal state of the active state and all widgets.
+            match states.top_mut() {
+                Some(state) => state.update(&mut widgets),
+                None => break 'running,
+            }
+
+            widgets.update(dt);
 
             // Draw the next frame onto the canvas.
             if widgets.is_invalidated() {
@@ -149,10 +251,21 @@ impl<W: Widget, S: State<W>> App<W, S> {
             }
         }
 
+        states.clear(&mut widgets);
+
         Ok(())
     }
 }
 
+impl<W: Widget + Debug> Debug for App<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct(stringify!(App))
+            .field("root_widget", &self.root_widget)
+            .field("error_message_box", &self.error_message_box)
+            .finish()
+    }
+}
+
 /// Sets the `window` fullscreen state and scales the root widget bounds to match the new size.
 ///
 /// When fullscreen mode is enabled or toggled on, this function always prefers native fullscreen
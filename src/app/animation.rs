@@ -0,0 +1,278 @@
+//! Frame-time-based animation of widget properties.
+//!
+//! Animations are driven by the time elapsed in between frames rather than the number of frames,
+//! so they always take the same amount of time, regardless of the frame rate.
+
+use std::time::Duration;
+
+use sdl2::pixels::Color;
+
+/// A curve which maps the linear progress of an animation to the progress of the animated value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Easing {
+    /// Progresses at a constant speed.
+    Linear,
+    /// Starts slowly and accelerates towards the end.
+    EaseIn,
+    /// Starts quickly and decelerates towards the end.
+    EaseOut,
+    /// Starts slowly, accelerates halfway through and decelerates towards the end.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the linear progress `t`, ranging from 0.0 to 1.0, onto the easing curve.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// Describes how long an animation takes, and which easing curve it follows.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Transition {
+    /// Total duration of the animation.
+    pub duration: Duration,
+    /// Easing curve of the animation.
+    pub easing: Easing,
+}
+
+impl Transition {
+    /// Applies changes immediately, without animating them.
+    pub const INSTANT: Transition = Transition::new(Duration::from_secs(0), Easing::Linear);
+
+    /// Creates a new `Transition` with the given duration and easing curve.
+    #[inline]
+    pub const fn new(duration: Duration, easing: Easing) -> Self {
+        Transition { duration, easing }
+    }
+
+    /// Returns `true` if changes are applied immediately.
+    #[inline]
+    pub fn is_instant(&self) -> bool {
+        self.duration == Duration::from_secs(0)
+    }
+}
+
+/// A value which can be linearly interpolated, and therefore animated.
+pub trait Lerp: Copy {
+    /// Interpolates between `from` and `to`, where `t` ranges from 0.0 to 1.0.
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for i32 {
+    #[inline]
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        from + ((to - from) as f32 * t).round() as i32
+    }
+}
+
+impl Lerp for u32 {
+    #[inline]
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        (from as f32 + (to as f32 - from as f32) * t).round() as u32
+    }
+}
+
+impl Lerp for u8 {
+    #[inline]
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        (from as f32 + (to as f32 - from as f32) * t).round() as u8
+    }
+}
+
+impl<A: Lerp, B: Lerp> Lerp for (A, B) {
+    #[inline]
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        (A::lerp(from.0, to.0, t), B::lerp(from.1, to.1, t))
+    }
+}
+
+impl Lerp for Color {
+    #[inline]
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Color::RGBA(
+            u8::lerp(from.r, to.r, t),
+            u8::lerp(from.g, to.g, t),
+            u8::lerp(from.b, to.b, t),
+            u8::lerp(from.a, to.a, t),
+        )
+    }
+}
+
+/// An in-flight animation of a single value from one state to another.
+#[derive(Clone, Debug)]
+pub struct Tween<T> {
+    from: T,
+    to: T,
+    elapsed: Duration,
+    transition: Transition,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// Creates a new `Tween` which animates from `from` to `to` using the given transition.
+    #[inline]
+    pub fn new(from: T, to: T, transition: Transition) -> Self {
+        Tween {
+            from,
+            to,
+            elapsed: Duration::from_secs(0),
+            transition,
+        }
+    }
+
+    /// Returns the current value of the animation.
+    pub fn value(&self) -> T {
+        if self.is_finished() {
+            return self.to;
+        }
+
+        let t = self.elapsed.as_secs_f32() / self.transition.duration.as_secs_f32();
+        T::lerp(self.from, self.to, self.transition.easing.apply(t))
+    }
+
+    /// Returns the value at which the animation ends.
+    #[inline]
+    pub fn target(&self) -> T {
+        self.to
+    }
+
+    /// Advances the animation by `dt` and returns its new value.
+    pub fn advance(&mut self, dt: Duration) -> T {
+        self.elapsed = (self.elapsed + dt).min(self.transition.duration);
+        self.value()
+    }
+
+    /// Returns `true` if the animation has reached its target value.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.transition.duration
+    }
+
+    /// Returns an animation which progresses in lockstep with this one, but with every value
+    /// transformed by `f`, e.g. offset by a fixed distance.
+    pub fn map<U: Lerp, F: Fn(T) -> U>(&self, f: F) -> Tween<U> {
+        Tween {
+            from: f(self.from),
+            to: f(self.to),
+            elapsed: self.elapsed,
+            transition: self.transition,
+        }
+    }
+}
+
+/// The in-flight animations of a widget's [`Properties`](super::Properties), if any.
+///
+/// Starting a new animation of a property replaces the in-flight one, which makes the property
+/// change course from its current value towards the new target value.
+#[derive(Debug, Default)]
+pub struct Animations {
+    pub(super) origin: Option<Tween<(i32, i32)>>,
+    pub(super) bounds: Option<Tween<(u32, u32)>>,
+    pub(super) color: Option<Tween<Color>>,
+    pub(super) opacity: Option<Tween<u8>>,
+}
+
+impl Animations {
+    /// Returns `true` if any property is currently being animated.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.origin.is_some()
+            || self.bounds.is_some()
+            || self.color.is_some()
+            || self.opacity.is_some()
+    }
+}
+
+/// Advances `tween` by `dt` and writes its new value into `value`, discarding the tween once it
+/// has finished.
+///
+/// Returns `true` if `value` was changed.
+pub(super) fn step<T: Lerp>(tween: &mut Option<Tween<T>>, value: &mut T, dt: Duration) -> bool {
+    match tween {
+        Some(t) => {
+            *value = t.advance(dt);
+            if t.is_finished() {
+                *tween = None;
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSITION: Transition = Transition::new(Duration::from_millis(100), Easing::Linear);
+
+    #[test]
+    fn eases_between_endpoints() {
+        for &easing in &[
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert!((easing.apply(0.5) - 0.5).abs() <= 0.4, "{:?}", easing);
+        }
+
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn advances_by_frame_time() {
+        let mut tween = Tween::new((0, 100), (100, 0), TRANSITION);
+        assert_eq!(tween.value(), (0, 100));
+
+        assert_eq!(tween.advance(Duration::from_millis(25)), (25, 75));
+        assert_eq!(tween.advance(Duration::from_millis(50)), (75, 25));
+        assert!(!tween.is_finished());
+
+        // Overshooting the duration clamps the value to the target.
+        assert_eq!(tween.advance(Duration::from_millis(50)), (100, 0));
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn interpolates_colors() {
+        let tween = Tween::new(Color::RGB(0, 0, 0), Color::RGB(200, 100, 50), TRANSITION);
+        let mut halfway = tween.clone();
+        halfway.advance(Duration::from_millis(50));
+        assert_eq!(halfway.value(), Color::RGB(100, 50, 25));
+    }
+
+    #[test]
+    fn steps_and_discards_finished_tweens() {
+        let mut tween = Some(Tween::new(0u8, 255, TRANSITION));
+        let mut opacity = 0;
+
+        assert!(step(&mut tween, &mut opacity, Duration::from_millis(60)));
+        assert_eq!(opacity, 153);
+        assert!(step(&mut tween, &mut opacity, Duration::from_millis(60)));
+        assert_eq!(opacity, 255);
+        assert!(tween.is_none());
+        assert!(!step(&mut tween, &mut opacity, Duration::from_millis(60)));
+    }
+
+    #[test]
+    fn maps_tweens_in_lockstep() {
+        let mut tween = Tween::new((0, 0), (100, 0), TRANSITION);
+        tween.advance(Duration::from_millis(40));
+
+        let mut child = tween.map(|(x, y)| (x + 10, y + 20));
+        assert_eq!(child.value(), (50, 20));
+        assert_eq!(child.target(), (110, 20));
+        assert_eq!(child.advance(Duration::from_millis(60)), (110, 20));
+    }
+}
@@ -0,0 +1,458 @@
+//! Pushdown stack of application states.
+//!
+//! The stack only relies on the [`Layers`] and [`StackState`] traits rather than on [`Widgets`]
+//! and [`State`] directly. Building a `Widgets` cache requires an SDL window and renderer, so this
+//! indirection is what allows the bookkeeping of the stack to be unit tested with mock layers and
+//! states. Both traits are implemented for the real types below, so the rest of the application
+//! never has to deal with them.
+
+use super::{State, Widget, Widgets};
+
+/// A stack of application states, of which only the topmost one is active.
+///
+/// Every state owns a layer of widgets in the [`Widgets`] cache, which is removed when the state
+/// is popped off the stack. The state at index `i` owns the layer at index `i + 1`, since the
+/// base layer only contains the root widget.
+///
+/// If a new state fails to initialize, its layer is removed again and the stack is left as it
+/// was before.
+pub(crate) struct StateStack<S: ?Sized> {
+    states: Vec<Box<S>>,
+}
+
+/// The layer operations of the [`Widgets`] cache which the [`StateStack`] relies on.
+pub(crate) trait Layers {
+    fn push_layer(&mut self) -> usize;
+    fn pop_layer(&mut self);
+    fn remove_layer(&mut self, layer: usize);
+    fn set_layer_visible(&mut self, layer: usize, visible: bool);
+}
+
+impl<W: Widget> Layers for Widgets<'_, W> {
+    #[inline]
+    fn push_layer(&mut self) -> usize {
+        Widgets::push_layer(self)
+    }
+
+    #[inline]
+    fn pop_layer(&mut self) {
+        Widgets::pop_layer(self)
+    }
+
+    #[inline]
+    fn remove_layer(&mut self, layer: usize) {
+        Widgets::remove_layer(self, layer)
+    }
+
+    #[inline]
+    fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        Widgets::set_layer_visible(self, layer, visible)
+    }
+}
+
+/// The lifecycle callbacks of a [`State`] which the [`StateStack`] calls, given its layers `L`.
+pub(crate) trait StackState<L> {
+    fn initialize(&mut self, layers: &mut L) -> anyhow::Result<()>;
+    fn on_enter(&mut self, layers: &mut L);
+    fn on_exit(&mut self, layers: &mut L);
+    fn on_pause(&mut self, layers: &mut L);
+    fn on_resume(&mut self, layers: &mut L);
+    fn is_overlay(&self) -> bool;
+}
+
+impl<'tc, W: Widget> StackState<Widgets<'tc, W>> for dyn State<W> {
+    #[inline]
+    fn initialize(&mut self, widgets: &mut Widgets<'tc, W>) -> anyhow::Result<()> {
+        State::initialize(self, widgets)
+    }
+
+    #[inline]
+    fn on_enter(&mut self, widgets: &mut Widgets<'tc, W>) {
+        State::on_enter(self, widgets)
+    }
+
+    #[inline]
+    fn on_exit(&mut self, widgets: &mut Widgets<'tc, W>) {
+        State::on_exit(self, widgets)
+    }
+
+    #[inline]
+    fn on_pause(&mut self, widgets: &mut Widgets<'tc, W>) {
+        State::on_pause(self, widgets)
+    }
+
+    #[inline]
+    fn on_resume(&mut self, widgets: &mut Widgets<'tc, W>) {
+        State::on_resume(self, widgets)
+    }
+
+    #[inline]
+    fn is_overlay(&self) -> bool {
+        State::is_overlay(self)
+    }
+}
+
+impl<S: ?Sized> StateStack<S> {
+    /// Creates a new empty `StateStack`.
+    pub fn new() -> Self {
+        StateStack { states: Vec::new() }
+    }
+
+    /// Returns a mutable reference to the active state, if any.
+    pub fn top_mut(&mut self) -> Option<&mut S> {
+        self.states.last_mut().map(|state| &mut **state)
+    }
+
+    /// Pauses the active state, if any, and pushes a new state on top of it.
+    ///
+    /// If the new state fails to initialize, the paused state is resumed and the error returned.
+    pub fn push<L: Layers>(&mut self, mut state: Box<S>, layers: &mut L) -> anyhow::Result<()>
+    where
+        S: StackState<L>,
+    {
+        if let Some(top) = self.states.last_mut() {
+            top.on_pause(layers);
+        }
+
+        layers.push_layer();
+        if let Err(e) = state.initialize(layers) {
+            layers.pop_layer();
+            if let Some(top) = self.states.last_mut() {
+                top.on_resume(layers);
+            }
+            return Err(e);
+        }
+
+        state.on_enter(layers);
+        self.states.push(state);
+        self.update_visibility(layers);
+
+        Ok(())
+    }
+
+    /// Pops the active state off the stack and resumes the state below it, if any.
+    pub fn pop<L: Layers>(&mut self, layers: &mut L)
+    where
+        S: StackState<L>,
+    {
+        self.exit_top(layers);
+
+        if let Some(top) = self.states.last_mut() {
+            top.on_resume(layers);
+        }
+
+        self.update_visibility(layers);
+    }
+
+    /// Replaces the active state with a new state, without resuming the state below it.
+    ///
+    /// The new state is initialized before the active state exits, so that the active state stays
+    /// in place if the new state fails to initialize.
+    pub fn switch<L: Layers>(&mut self, mut state: Box<S>, layers: &mut L) -> anyhow::Result<()>
+    where
+        S: StackState<L>,
+    {
+        let layer = layers.push_layer();
+        if let Err(e) = state.initialize(layers) {
+            layers.pop_layer();
+            return Err(e);
+        }
+
+        if let Some(mut top) = self.states.pop() {
+            top.on_exit(layers);
+            layers.remove_layer(layer - 1);
+        }
+
+        state.on_enter(layers);
+        self.states.push(state);
+        self.update_visibility(layers);
+
+        Ok(())
+    }
+
+    /// Pops every state off the stack, from the top down.
+    pub fn clear<L: Layers>(&mut self, layers: &mut L)
+    where
+        S: StackState<L>,
+    {
+        while !self.states.is_empty() {
+            self.exit_top(layers);
+        }
+    }
+
+    /// Pops the active state off the stack and removes its widgets.
+    fn exit_top<L: Layers>(&mut self, layers: &mut L)
+    where
+        S: StackState<L>,
+    {
+        if let Some(mut state) = self.states.pop() {
+            state.on_exit(layers);
+            layers.pop_layer();
+        }
+    }
+
+    /// Shows the layers of the active state and every overlay state directly beneath it, and
+    /// hides all the others.
+    fn update_visibility<L: Layers>(&self, layers: &mut L)
+    where
+        S: StackState<L>,
+    {
+        let mut visible = true;
+        for (i, state) in self.states.iter().enumerate().rev() {
+            layers.set_layer_visible(i + 1, visible);
+            visible = visible && state.is_overlay();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::anyhow;
+
+    /// Layers holding the names of the states which initialized into them, and whether they are
+    /// visible.
+    struct MockLayers {
+        layers: Vec<(Vec<&'static str>, bool)>,
+        events: Vec<String>,
+    }
+
+    impl MockLayers {
+        fn new() -> Self {
+            MockLayers {
+                layers: vec![(Vec::new(), true)],
+                events: Vec::new(),
+            }
+        }
+
+        /// Returns the contents of every layer above the base layer, and whether it is visible.
+        fn contents(&self) -> Vec<(Vec<&'static str>, bool)> {
+            self.layers[1..].to_vec()
+        }
+
+        fn take_events(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    impl Layers for MockLayers {
+        fn push_layer(&mut self) -> usize {
+            self.layers.push((Vec::new(), true));
+            self.layers.len() - 1
+        }
+
+        fn pop_layer(&mut self) {
+            self.remove_layer(self.layers.len() - 1);
+        }
+
+        fn remove_layer(&mut self, layer: usize) {
+            if layer > 0 && layer < self.layers.len() {
+                self.layers.remove(layer);
+            }
+        }
+
+        fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+            self.layers[layer].1 = visible;
+        }
+    }
+
+    struct MockState {
+        name: &'static str,
+        is_overlay: bool,
+        fails: bool,
+    }
+
+    impl MockState {
+        fn new(name: &'static str) -> Box<Self> {
+            Box::new(MockState {
+                name,
+                is_overlay: false,
+                fails: false,
+            })
+        }
+
+        fn overlay(name: &'static str) -> Box<Self> {
+            Box::new(MockState {
+                name,
+                is_overlay: true,
+                fails: false,
+            })
+        }
+
+        fn failing(name: &'static str) -> Box<Self> {
+            Box::new(MockState {
+                name,
+                is_overlay: false,
+                fails: true,
+            })
+        }
+
+        fn log(&self, layers: &mut MockLayers, event: &str) {
+            layers.events.push(format!("{} {}", self.name, event));
+        }
+    }
+
+    impl StackState<MockLayers> for MockState {
+        fn initialize(&mut self, layers: &mut MockLayers) -> anyhow::Result<()> {
+            // Like widgets inserted below the root, the state builds into the topmost layer.
+            let name = self.name;
+            layers.layers.last_mut().unwrap().0.push(name);
+            self.log(layers, "initialize");
+            if self.fails {
+                Err(anyhow!("{} failed to initialize", name))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn on_enter(&mut self, layers: &mut MockLayers) {
+            self.log(layers, "enter");
+        }
+
+        fn on_exit(&mut self, layers: &mut MockLayers) {
+            self.log(layers, "exit");
+        }
+
+        fn on_pause(&mut self, layers: &mut MockLayers) {
+            self.log(layers, "pause");
+        }
+
+        fn on_resume(&mut self, layers: &mut MockLayers) {
+            self.log(layers, "resume");
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.is_overlay
+        }
+    }
+
+    fn names(stack: &StateStack<MockState>) -> Vec<&'static str> {
+        stack.states.iter().map(|state| state.name).collect()
+    }
+
+    #[test]
+    fn pushes_and_pops_states() {
+        let mut layers = MockLayers::new();
+        let mut stack = StateStack::new();
+
+        stack.push(MockState::new("menu"), &mut layers).unwrap();
+        stack
+            .push(MockState::overlay("dialog"), &mut layers)
+            .unwrap();
+        assert_eq!(
+            layers.take_events(),
+            [
+                "menu initialize",
+                "menu enter",
+                "menu pause",
+                "dialog initialize",
+                "dialog enter"
+            ]
+        );
+        // The menu stays visible beneath the dialog.
+        assert_eq!(
+            layers.contents(),
+            [(vec!["menu"], true), (vec!["dialog"], true)]
+        );
+
+        stack.push(MockState::new("player"), &mut layers).unwrap();
+        assert_eq!(names(&stack), ["menu", "dialog", "player"]);
+        assert_eq!(
+            layers.contents(),
+            [
+                (vec!["menu"], false),
+                (vec!["dialog"], false),
+                (vec!["player"], true)
+            ]
+        );
+
+        layers.take_events();
+        stack.pop(&mut layers);
+        assert_eq!(layers.take_events(), ["player exit", "dialog resume"]);
+        assert_eq!(names(&stack), ["menu", "dialog"]);
+        assert_eq!(
+            layers.contents(),
+            [(vec!["menu"], true), (vec!["dialog"], true)]
+        );
+
+        stack.clear(&mut layers);
+        assert_eq!(layers.take_events(), ["dialog exit", "menu exit"]);
+        assert!(stack.top_mut().is_none());
+        assert!(layers.contents().is_empty());
+    }
+
+    #[test]
+    fn switches_states() {
+        let mut layers = MockLayers::new();
+        let mut stack = StateStack::new();
+
+        stack.push(MockState::new("menu"), &mut layers).unwrap();
+        stack.push(MockState::new("details"), &mut layers).unwrap();
+        layers.take_events();
+
+        stack.switch(MockState::new("player"), &mut layers).unwrap();
+        assert_eq!(
+            layers.take_events(),
+            ["player initialize", "details exit", "player enter"]
+        );
+        assert_eq!(names(&stack), ["menu", "player"]);
+        // The widgets of the new state take over the layer of the state it replaced.
+        assert_eq!(
+            layers.contents(),
+            [(vec!["menu"], false), (vec!["player"], true)]
+        );
+
+        stack.pop(&mut layers);
+        assert_eq!(layers.take_events(), ["player exit", "menu resume"]);
+        assert_eq!(layers.contents(), [(vec!["menu"], true)]);
+
+        // Switching on an empty stack is the same as pushing.
+        let mut stack = StateStack::new();
+        let mut layers = MockLayers::new();
+        stack.switch(MockState::new("menu"), &mut layers).unwrap();
+        assert_eq!(names(&stack), ["menu"]);
+        assert_eq!(layers.contents(), [(vec!["menu"], true)]);
+    }
+
+    #[test]
+    fn restores_stack_if_initialization_fails() {
+        let mut layers = MockLayers::new();
+        let mut stack = StateStack::new();
+
+        stack.push(MockState::new("menu"), &mut layers).unwrap();
+        stack
+            .push(MockState::overlay("dialog"), &mut layers)
+            .unwrap();
+        layers.take_events();
+
+        assert!(stack
+            .push(MockState::failing("player"), &mut layers)
+            .is_err());
+        assert_eq!(
+            layers.take_events(),
+            ["dialog pause", "player initialize", "dialog resume"]
+        );
+        assert_eq!(names(&stack), ["menu", "dialog"]);
+        assert_eq!(
+            layers.contents(),
+            [(vec!["menu"], true), (vec!["dialog"], true)]
+        );
+
+        assert!(stack
+            .switch(MockState::failing("player"), &mut layers)
+            .is_err());
+        assert_eq!(layers.take_events(), ["player initialize"]);
+        assert_eq!(names(&stack), ["menu", "dialog"]);
+        assert_eq!(
+            layers.contents(),
+            [(vec!["menu"], true), (vec!["dialog"], true)]
+        );
+
+        let mut stack = StateStack::new();
+        let mut layers = MockLayers::new();
+        assert!(stack.push(MockState::failing("menu"), &mut layers).is_err());
+        assert!(stack.top_mut().is_none());
+        assert!(layers.contents().is_empty());
+    }
+}
@@ -3,15 +3,25 @@
 use std::cell::{Ref, RefCell, RefMut};
 use std::fmt::{self, Debug, Formatter};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use fnv::FnvHashMap as HashMap;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
 use sdl2::ttf::{FontStyle, Sdl2TtfContext};
 use sdl2::video::{Window, WindowContext};
 
+use self::slots::Slots;
+use self::text::CachedFont;
+use super::animation::{self, Animations, Transition, Tween};
+
+pub use self::text::Text;
+
+mod slots;
+mod text;
+
 /// A trait which describes a rectangular UI widget.
 pub trait Widget {
     /// Returns an immutable reference the properties of the widget.
@@ -20,8 +30,11 @@ pub trait Widget {
     /// Returns a mutable reference to the properties of the widget.
     fn properties_mut(&mut self) -> &mut Properties;
 
-    /// Executes once on every frame, useful for animations or updating internal state.
-    fn update(&mut self) {}
+    /// Executes once on every frame, useful for custom animations or updating internal state.
+    ///
+    /// `dt` is the time elapsed since the previous frame. Animations started with the
+    /// `animate_*()` methods have already been advanced by the time this method is called.
+    fn update(&mut self, _dt: Duration) {}
 
     /// Renders the widget into the given [`Texture`](sdl2::render::Texture).
     fn draw(&mut self, ctx: &mut Context, target: &mut Texture) -> anyhow::Result<()>;
@@ -50,30 +63,105 @@ pub trait Widget {
         self.properties().border
     }
 
+    /// Returns the opacity of the widget, ranging from 0 (transparent) to 255 (opaque).
+    #[inline]
+    fn opacity(&self) -> u8 {
+        self.properties().opacity
+    }
+
+    /// Returns the location at which the widget will come to rest once its animation finishes.
+    ///
+    /// Returns the current location if the widget is not moving.
+    #[inline]
+    fn target_origin(&self) -> (i32, i32) {
+        let properties = self.properties();
+        properties
+            .animations
+            .origin
+            .as_ref()
+            .map_or(properties.origin, Tween::target)
+    }
+
+    /// Returns the size at which the widget will come to rest once its animation finishes.
+    ///
+    /// Returns the current size if the widget is not being resized.
+    #[inline]
+    fn target_bounds(&self) -> (u32, u32) {
+        let properties = self.properties();
+        properties
+            .animations
+            .bounds
+            .as_ref()
+            .map_or(properties.bounds, Tween::target)
+    }
+
     /// Returns whether this widget is hidden.
     #[inline]
     fn is_hidden(&self) -> bool {
         self.properties().hidden
     }
 
-    /// Moves the widget to pixel coordinate (X, Y).
+    /// Moves the widget to pixel coordinate (X, Y), stopping any animation of its location.
     #[inline]
     fn set_origin(&mut self, x: i32, y: i32) -> &mut Self {
-        self.properties_mut().origin = (x, y);
-        self.invalidate()
+        self.animate_origin(x, y, Transition::INSTANT)
     }
 
-    /// Resizes the widget to the given dimensions, in pixels.
+    /// Resizes the widget to the given dimensions, in pixels, stopping any animation of its size.
     #[inline]
     fn set_bounds(&mut self, width: u32, height: u32) -> &mut Self {
-        self.properties_mut().bounds = (width, height);
-        self.invalidate()
+        self.animate_bounds(width, height, Transition::INSTANT)
     }
 
-    /// Sets the color attribute of the widget.
+    /// Sets the color attribute of the widget, stopping any animation of its color.
     #[inline]
     fn set_color(&mut self, color: Color) -> &mut Self {
-        self.properties_mut().color = color;
+        self.animate_color(color, Transition::INSTANT)
+    }
+
+    /// Sets the opacity of the widget, stopping any animation of its opacity.
+    #[inline]
+    fn set_opacity(&mut self, opacity: u8) -> &mut Self {
+        self.animate_opacity(opacity, Transition::INSTANT)
+    }
+
+    /// Gradually moves the widget to pixel coordinate (X, Y).
+    ///
+    /// If the widget is already moving, it changes course towards the new location instead.
+    #[inline]
+    fn animate_origin(&mut self, x: i32, y: i32, transition: Transition) -> &mut Self {
+        let properties = self.properties_mut();
+        properties.animations.origin = animate(&mut properties.origin, (x, y), transition);
+        self.invalidate()
+    }
+
+    /// Gradually resizes the widget to the given dimensions, in pixels.
+    ///
+    /// If the widget is already being resized, it changes course towards the new size instead.
+    #[inline]
+    fn animate_bounds(&mut self, width: u32, height: u32, transition: Transition) -> &mut Self {
+        let properties = self.properties_mut();
+        properties.animations.bounds = animate(&mut properties.bounds, (width, height), transition);
+        self.invalidate()
+    }
+
+    /// Gradually changes the color attribute of the widget.
+    ///
+    /// If the color is already changing, it changes course towards the new color instead.
+    #[inline]
+    fn animate_color(&mut self, color: Color, transition: Transition) -> &mut Self {
+        let properties = self.properties_mut();
+        properties.animations.color = animate(&mut properties.color, color, transition);
+        self.invalidate()
+    }
+
+    /// Gradually changes the opacity of the widget.
+    ///
+    /// If the opacity is already changing, it changes course towards the new opacity instead.
+    #[inline]
+    fn animate_opacity(&mut self, opacity: u8, transition: Transition) -> &mut Self {
+        let properties = self.properties_mut();
+        properties.animations.opacity = animate(&mut properties.opacity, opacity, transition);
         self.invalidate()
     }
 
@@ -91,11 +179,11 @@ pub trait Widget {
         self.invalidate()
     }
 
-    /// Toggles the visibility of the widget.
+    /// Toggles the visibility of the widget. Its children are not affected.
     #[inline]
     fn set_hidden(&mut self, hidden: bool) -> &mut Self {
         self.properties_mut().hidden = hidden;
-        self
+        self.invalidate()
     }
 
     /// Forces the widget to be redrawn on the next frame.
@@ -117,10 +205,38 @@ pub struct Properties {
     pub color: Color,
     /// Border color and thickness, in pixels, if any.
     pub border: Option<(Color, u8)>,
+    /// Opacity of the widget, ranging from 0 (transparent) to 255 (opaque).
+    pub opacity: u8,
     /// Indicates that the widget should not be rendered.
     pub hidden: bool,
     /// Indicates whether the [`Widget::draw()`] method needs to be called.
     pub invalidated: bool,
+    /// In-flight animations of the properties above, if any.
+    pub animations: Animations,
+}
+
+impl Properties {
+    /// Advances all in-flight animations by `dt`, invalidating the widget if any have progressed.
+    fn advance_animations(&mut self, dt: Duration) {
+        let Properties {
+            origin,
+            bounds,
+            color,
+            opacity,
+            animations,
+            ..
+        } = self;
+
+        // Every animation must be advanced, so these must not short-circuit.
+        let changed = animation::step(&mut animations.origin, origin, dt)
+            | animation::step(&mut animations.bounds, bounds, dt)
+            | animation::step(&mut animations.color, color, dt)
+            | animation::step(&mut animations.opacity, opacity, dt);
+
+        if changed {
+            self.invalidated = true;
+        }
+    }
 }
 
 impl Default for Properties {
@@ -131,12 +247,30 @@ impl Default for Properties {
             bounds: (0, 0),
             color: Color::WHITE,
             border: None,
+            opacity: 255,
             hidden: false,
             invalidated: true,
+            animations: Animations::default(),
         }
     }
 }
 
+/// Starts animating `value` towards `target`, replacing any in-flight animation of it.
+///
+/// If the transition is instant, `value` is set to `target` right away and `None` is returned.
+fn animate<T: animation::Lerp>(
+    value: &mut T,
+    target: T,
+    transition: Transition,
+) -> Option<Tween<T>> {
+    if transition.is_instant() {
+        *value = target;
+        None
+    } else {
+        Some(Tween::new(*value, target, transition))
+    }
+}
+
 /// A shared context passed to every [`Widget::draw()`] call.
 pub struct Context<'a, 'tc> {
     /// Handle to the window canvas.
@@ -154,10 +288,37 @@ impl<'a, 'tc> Debug for Context<'a, 'tc> {
 }
 
 /// A shared cache of drawable UI widgets.
+///
+/// Widgets are grouped into a stack of layers, one for every [`State`](super::State) on the
+/// application state stack. Widgets inserted directly below the root belong to the topmost layer,
+/// and all other widgets belong to the same layer as their parent. The root widget itself belongs
+/// to the base layer at index 0, which is always visible.
+///
+/// Widgets which lie entirely outside the root widget are not drawn at all. Widgets can also be
+/// [virtualized](Widgets::set_virtualized()), so that their descendants only keep their textures
+/// while they are close to the root widget.
+///
+/// Rather than redrawing the entire window on every frame, the regions of the window covered by
+/// invalidated widgets are marked as damaged, and only these regions are recomposited onto a
+/// persistent backbuffer.
+///
+/// Widgets can be removed from the cache at any time, along with all of their descendants. The
+/// IDs of removed widgets are never reused, so accessing a removed widget is detected rather than
+/// silently referring to another widget.
 pub struct Widgets<'tc, W> {
-    cache: HashMap<WidgetId, CacheEntry<'tc, W>>,
-    next_id: u32,
+    cache: Slots<CacheEntry<'tc, W>>,
     textures: Textures<'tc>,
+    /// Textures released by the descendants of virtualized widgets, ready to be reused.
+    recycled: TexturePool<'tc>,
+    /// Texture holding the contents of the window, of which only damaged regions are redrawn.
+    backbuffer: WidgetTexture<'tc>,
+    /// Regions of the window which must be redrawn on the next frame, in addition to the regions
+    /// covered by invalidated widgets, e.g. after a widget has been removed.
+    damage: Vec<Rect>,
+    /// Widgets drawn onto the backbuffer on the last frame, and where, from bottom to top.
+    draw_order: Vec<(WidgetId, Rect)>,
+    /// Visibility of every layer, from the bottom to the top of the stack.
+    layers: Vec<bool>,
 }
 
 impl<'tc, W: Widget> Widgets<'tc, W> {
@@ -166,14 +327,18 @@ impl<'tc, W: Widget> Widgets<'tc, W> {
         // Mark the widget for initial drawing.
         root_widget.invalidate();
 
-        let root_id = WidgetId::root();
-        let mut cache = HashMap::default();
-        cache.insert(root_id, CacheEntry::new(root_widget, root_id));
+        let mut cache = Slots::new();
+        let root_id = cache.insert(CacheEntry::new(root_widget, WidgetId::root(), 0));
+        debug_assert_eq!(root_id, WidgetId::root());
 
         Widgets {
             cache,
-            next_id: 1,
             textures,
+            recycled: TexturePool::default(),
+            backbuffer: WidgetTexture::default(),
+            damage: Vec::new(),
+            draw_order: Vec::new(),
+            layers: vec![true],
         }
     }
 
@@ -185,105 +350,420 @@ impl<'tc, W: Widget> Widgets<'tc, W> {
 
     /// Inserts a widget into the cache, marked as a child of `parent`.
     ///
+    /// If the parent is currently moving, the widget moves along with it, keeping its current
+    /// distance to the parent.
+    ///
     /// Returns the unique ID of the inserted widget.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` has been removed.
     pub fn insert(&mut self, mut widget: W, parent: WidgetId) -> WidgetId {
         // Mark the widget for initial drawing.
         widget.invalidate();
 
-        let id = WidgetId(self.next_id);
-        self.cache
-            .get_mut(&parent)
-            .expect("parent always exists")
-            .children
-            .push(id);
+        let layer = {
+            let parent_entry = self.entry(parent);
+            let parent_widget = parent_entry.widget.borrow();
+            let (parent_x, parent_y) = parent_widget.origin();
+            if let Some(ref tween) = parent_widget.properties().animations.origin {
+                let (x, y) = widget.origin();
+                let (dx, dy) = (x - parent_x, y - parent_y);
+                widget.properties_mut().animations.origin =
+                    Some(tween.map(|(x, y)| (x + dx, y + dy)));
+            }
+
+            self.child_layer(parent)
+        };
 
-        self.cache.insert(id, CacheEntry::new(widget, parent));
-        self.next_id += 1;
+        let id = self.cache.insert(CacheEntry::new(widget, parent, layer));
+        self.entry_mut(parent).children.push(id);
 
         id
     }
 
+    /// Removes a widget and all of its descendants from the cache, freeing their textures.
+    ///
+    /// Returns the removed widget, or `None` if `id` refers to the root widget or to a widget
+    /// which has already been removed.
+    pub fn remove(&mut self, id: WidgetId) -> Option<W> {
+        if id == WidgetId::root() {
+            return None;
+        }
+
+        let entry = self.cache.remove(id)?;
+        self.damage.extend(entry.drawn);
+        if let Some(parent) = self.cache.get_mut(entry.parent) {
+            parent.children.retain(|&child_id| child_id != id);
+            parent.widget.get_mut().invalidate();
+        }
+
+        self.remove_descendants(&entry.children);
+        Some(entry.widget.into_inner())
+    }
+
+    /// Removes all children of the widget named `id` and their descendants from the cache,
+    /// freeing their textures. The widget itself is kept.
+    ///
+    /// Does nothing if the widget has been removed.
+    pub fn clear_children(&mut self, id: WidgetId) {
+        let children = match self.cache.get_mut(id) {
+            Some(entry) => {
+                entry.widget.get_mut().invalidate();
+                std::mem::take(&mut entry.children)
+            }
+            None => return,
+        };
+
+        self.remove_descendants(&children);
+    }
+
+    /// Moves a widget and all of its descendants to the end of the children of `new_parent`.
+    ///
+    /// The widgets keep their current location on the screen, and move to the same layer as their
+    /// new parent.
+    ///
+    /// Returns `Err` if either widget has been removed, if `id` refers to the root widget, or if
+    /// `new_parent` is the widget itself or one of its descendants.
+    pub fn reparent(&mut self, id: WidgetId, new_parent: WidgetId) -> anyhow::Result<()> {
+        if id == WidgetId::root() {
+            return Err(anyhow!("the root widget cannot be reparented"));
+        } else if !self.contains(id) || !self.contains(new_parent) {
+            return Err(anyhow!(
+                "widget {:?} or {:?} has been removed",
+                id,
+                new_parent
+            ));
+        } else if self.is_descendant(new_parent, id) {
+            return Err(anyhow!("widget {:?} cannot become its own descendant", id));
+        }
+
+        let old_parent = std::mem::replace(&mut self.entry_mut(id).parent, new_parent);
+        let old_parent = self.entry_mut(old_parent);
+        old_parent.children.retain(|&child_id| child_id != id);
+        old_parent.widget.get_mut().invalidate();
+
+        self.entry_mut(new_parent).children.push(id);
+        let layer = self.child_layer(new_parent);
+        self.set_layer(id, layer);
+
+        Ok(())
+    }
+
+    /// Puts the widget into virtualized mode if `margin` is `Some`, or takes it out of virtualized
+    /// mode if `margin` is `None`.
+    ///
+    /// Descendants of a virtualized widget only keep their textures while they are within `margin`
+    /// pixels of the root widget. Textures of descendants which are further away are recycled for
+    /// the descendants coming into view, so long rows of widgets only keep a handful of textures
+    /// alive at any time. Does nothing if the widget has been removed.
+    pub fn set_virtualized(&mut self, id: WidgetId, margin: Option<u32>) {
+        if let Some(entry) = self.cache.get_mut(id) {
+            entry.virtualized = margin;
+        }
+    }
+
+    /// Returns `true` if the widget named `id` has not been removed from the cache.
+    #[inline]
+    pub fn contains(&self, id: WidgetId) -> bool {
+        self.cache.get(id).is_some()
+    }
+
     /// Returns an immutable reference to a widget in the cache.
     ///
     /// # Panics
     ///
-    /// Panics if the same widget is already borrowed mutably.
+    /// Panics if the widget has been removed, or if the same widget is already borrowed mutably.
     pub fn get(&self, id: WidgetId) -> Ref<W> {
-        self.cache[&id].widget.borrow()
+        self.entry(id).widget.borrow()
     }
 
     /// Returns a mutable reference to a widget in the cache.
     ///
     /// # Panics
     ///
-    /// Panics if the same widget is already borrowed immutably.
+    /// Panics if the widget has been removed, or if the same widget is already borrowed
+    /// immutably.
     pub fn get_mut(&self, id: WidgetId) -> RefMut<W> {
-        self.cache[&id].widget.borrow_mut()
+        self.entry(id).widget.borrow_mut()
+    }
+
+    /// Returns an immutable reference to a widget in the cache, or `None` if it has been removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same widget is already borrowed mutably.
+    pub fn try_get(&self, id: WidgetId) -> Option<Ref<'_, W>> {
+        self.cache.get(id).map(|e| e.widget.borrow())
+    }
+
+    /// Returns a mutable reference to a widget in the cache, or `None` if it has been removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same widget is already borrowed immutably.
+    pub fn try_get_mut(&self, id: WidgetId) -> Option<RefMut<'_, W>> {
+        self.cache.get(id).map(|e| e.widget.borrow_mut())
     }
 
     /// Returns an immutable slice containing the children of the widget named `id`.
+    ///
+    /// The slice is empty if the widget has been removed.
     pub fn get_children_of(&self, id: WidgetId) -> &[WidgetId] {
-        &self.cache[&id].children[..]
+        self.cache.get(id).map_or(&[], |e| &e.children[..])
     }
 
-    /// Applies a delta X/Y translation to a widget and all of its children.
-    pub fn translate(&self, id: WidgetId, dx: i32, dy: i32) {
+    /// Applies a delta X/Y translation to a widget and all of its children, animated using the
+    /// given transition.
+    ///
+    /// The translation is relative to where the widgets come to rest, so translating widgets which
+    /// are already moving makes them change course rather than stopping short of their target.
+    ///
+    /// Does nothing if the widget has been removed.
+    pub fn translate(&self, id: WidgetId, dx: i32, dy: i32, transition: Transition) {
         if dx == 0 && dy == 0 {
             return;
         }
 
-        let mut widget = self.get_mut(id);
-        let (x, y) = widget.origin();
-        widget.set_origin(x + dx, y + dy);
+        let mut widget = match self.try_get_mut(id) {
+            Some(widget) => widget,
+            None => return,
+        };
+        let (x, y) = widget.target_origin();
+        widget.animate_origin(x + dx, y + dy, transition);
 
         for child_id in self.get_children_of(id) {
             if *child_id != id {
-                self.translate(*child_id, dx, dy);
+                self.translate(*child_id, dx, dy, transition);
             }
         }
     }
 
-    /// Ticks the internal state of all widgets by one frame.
-    pub(crate) fn update(&mut self) {
+    /// Pushes a new, visible layer onto the stack, into which new widgets are inserted.
+    ///
+    /// Returns the index of the new layer.
+    pub(crate) fn push_layer(&mut self) -> usize {
+        self.layers.push(true);
+        self.layers.len() - 1
+    }
+
+    /// Pops the topmost layer off the stack, removing all of its widgets from the cache.
+    ///
+    /// The base layer containing the root widget is never popped.
+    pub(crate) fn pop_layer(&mut self) {
+        self.remove_layer(self.layers.len() - 1);
+    }
+
+    /// Removes the given layer from the stack along with all of its widgets, and moves the layers
+    /// above it down by one.
+    ///
+    /// The base layer containing the root widget is never removed.
+    pub(crate) fn remove_layer(&mut self, layer: usize) {
+        if layer == 0 || layer >= self.layers.len() {
+            return;
+        }
+
+        self.layers.remove(layer);
+
+        // Only children of the root widget may belong to a different layer than their parent.
+        let mut children = std::mem::take(&mut self.entry_mut(WidgetId::root()).children);
+        children.retain(|&id| matches!(self.cache.get(id), Some(e) if e.layer != layer));
+        self.cache.retain(|entry| entry.layer != layer);
+        for entry in self.cache.values_mut().filter(|entry| entry.layer > layer) {
+            entry.layer -= 1;
+        }
+
+        self.entry_mut(WidgetId::root()).children = children;
+        self.damage.push(self.viewport());
+    }
+
+    /// Shows or hides all widgets belonging to the given layer.
+    pub(crate) fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        if let Some(v) = self.layers.get_mut(layer).filter(|v| **v != visible) {
+            *v = visible;
+            self.damage.push(self.viewport());
+        }
+    }
+
+    /// Ticks the internal state and animations of all widgets by one frame, given the time `dt`
+    /// elapsed since the previous frame.
+    pub(crate) fn update(&mut self, dt: Duration) {
         for widget in self.cache.values_mut().map(|e| e.widget.get_mut()) {
-            widget.update();
+            widget.properties_mut().advance_animations(dt);
+            widget.update(dt);
         }
     }
 
-    /// Returns whether any widgets have indicated that they need to be redrawn.
+    /// Returns whether any region of the window needs to be redrawn.
+    ///
+    /// Widgets outside of the root widget are only redrawn once they come into view, unless they
+    /// were drawn on the previous frame and still need to be erased.
     pub(crate) fn is_invalidated(&self) -> bool {
-        self.cache
-            .values()
-            .any(|e| e.widget.borrow().properties().invalidated)
+        let viewport = self.viewport();
+        !self.damage.is_empty()
+            || self
+                .cache
+                .values()
+                .any(|e| e.damage(viewport, self.layers[e.layer]) != (None, None))
     }
 
     /// Renders all the widgets in the cache to the canvas.
     ///
-    /// This method should only redraw widgets that requested it, for the sake of efficiency.
+    /// Only widgets which requested it are redrawn, and only the damaged regions of the window
+    /// are recomposited from the widget textures. The backbuffer covers the root widget, which is
+    /// anchored at the top-left corner of the window.
     pub(crate) fn draw(&mut self, canvas: &mut Canvas<Window>) -> anyhow::Result<()> {
-        canvas.set_draw_color(Color::RGBA(255, 255, 255, 255));
-        canvas.clear();
+        let viewport = self.viewport();
+        let mut damage = std::mem::take(&mut self.damage);
+
+        // The entire window must be redrawn if the backbuffer has just been created or resized.
+        let (width, height) = (viewport.width(), viewport.height());
+        if !self.backbuffer.matches(width, height) {
+            damage.push(viewport);
+        }
+
+        for entry in self.cache.values() {
+            let (drawn, shown) = entry.damage(viewport, self.layers[entry.layer]);
+            damage.extend(drawn);
+            damage.extend(shown);
+        }
+
+        merge_damage(&mut damage, viewport);
 
-        self.draw_widget(self.root(), canvas)?;
+        // Redraw the textures of invalidated widgets before compositing any of them.
+        let mut draw_order = std::mem::take(&mut self.draw_order);
+        draw_order.clear();
+        self.draw_widget(self.root(), canvas, viewport, None, &mut draw_order)?;
+
+        let cache = &self.cache;
+        let creator = self.textures.creator;
+        let backbuffer =
+            self.backbuffer
+                .create_or_resize(creator, &mut self.recycled, width, height)?;
+
+        let mut result = Ok(());
+        canvas.with_texture_canvas(backbuffer, |target| {
+            result = composite(target, &damage, &draw_order, cache);
+        })?;
+        result.map_err(Error::msg)?;
+
+        backbuffer.set_blend_mode(BlendMode::None);
+        canvas
+            .copy(backbuffer, None, viewport)
+            .map_err(Error::msg)?;
         canvas.present();
 
+        damage.clear();
+        self.damage = damage;
+        self.draw_order = draw_order;
+
         Ok(())
     }
 
-    fn draw_widget(&mut self, id: WidgetId, canvas: &mut Canvas<Window>) -> anyhow::Result<()> {
-        let (widget, texture) = self
-            .cache
-            .get_mut(&id)
-            .map(|e| (e.widget.get_mut(), &mut e.texture))
-            .unwrap();
+    /// Returns the area covered by the root widget, outside of which widgets are not drawn.
+    fn viewport(&self) -> Rect {
+        widget_rect(&*self.get(WidgetId::root()))
+    }
+
+    fn entry(&self, id: WidgetId) -> &CacheEntry<'tc, W> {
+        match self.cache.get(id) {
+            Some(entry) => entry,
+            None => panic!("widget {:?} has been removed", id),
+        }
+    }
+
+    fn entry_mut(&mut self, id: WidgetId) -> &mut CacheEntry<'tc, W> {
+        match self.cache.get_mut(id) {
+            Some(entry) => entry,
+            None => panic!("widget {:?} has been removed", id),
+        }
+    }
+
+    /// Returns the index of the layer to which new children of `parent` belong.
+    fn child_layer(&self, parent: WidgetId) -> usize {
+        if parent == WidgetId::root() {
+            self.layers.len() - 1
+        } else {
+            self.entry(parent).layer
+        }
+    }
+
+    /// Moves a widget and all of its descendants to the given layer, and redraws them.
+    fn set_layer(&mut self, id: WidgetId, layer: usize) {
+        let entry = self.entry_mut(id);
+        entry.layer = layer;
+        entry.widget.get_mut().invalidate();
+        for child_id in entry.children.clone() {
+            self.set_layer(child_id, layer);
+        }
+    }
+
+    /// Returns `true` if the widget named `id` is `ancestor` itself or one of its descendants.
+    fn is_descendant(&self, mut id: WidgetId, ancestor: WidgetId) -> bool {
+        loop {
+            if id == ancestor {
+                return true;
+            } else if id == WidgetId::root() {
+                return false;
+            }
+
+            id = self.entry(id).parent;
+        }
+    }
+
+    /// Removes the given widgets and all of their descendants from the cache.
+    fn remove_descendants(&mut self, children: &[WidgetId]) {
+        for &child_id in children {
+            if let Some(entry) = self.cache.remove(child_id) {
+                self.damage.extend(entry.drawn);
+                self.remove_descendants(&entry.children);
+            }
+        }
+    }
 
-        let (x, y) = widget.origin();
+    /// Redraws the textures of a widget and its descendants if they are invalidated, skipping
+    /// those outside of `viewport`, and appends the widgets to be composited to `draw_order`.
+    ///
+    /// If any ancestor of the widget is virtualized, `margin` is the margin of the closest one.
+    fn draw_widget(
+        &mut self,
+        id: WidgetId,
+        canvas: &mut Canvas<Window>,
+        viewport: Rect,
+        margin: Option<u32>,
+        draw_order: &mut Vec<(WidgetId, Rect)>,
+    ) -> anyhow::Result<()> {
+        let layers = &self.layers;
+        let CacheEntry {
+            widget,
+            texture,
+            drawn,
+            virtualized,
+            ..
+        } = match self.cache.get_mut(id) {
+            Some(e) if layers[e.layer] => e,
+            _ => return Ok(()),
+        };
+
+        let widget = widget.get_mut();
+        let rect = widget_rect(&*widget);
         let (width, height) = widget.bounds();
+        let is_on_screen = overlaps(rect, viewport);
+
+        // Release the texture of a virtualized widget once it is too far away from the viewport.
+        // The widget has to be drawn from scratch once it comes close enough again.
+        if let Some(margin) = margin {
+            if !is_near(rect, viewport, margin) && texture.release(&mut self.recycled) {
+                widget.invalidate();
+            }
+        }
 
-        if !widget.is_hidden() {
+        *drawn = Some(rect).filter(|_| !widget.is_hidden() && is_on_screen);
+        if drawn.is_some() {
             // Retrieve base widget texture, resizing if bounds have changed.
             let textures = &mut self.textures;
-            let target = texture.create_or_resize(textures.creator, width, height)?;
+            let recycled = &mut self.recycled;
+            let target = texture.create_or_resize(textures.creator, recycled, width, height)?;
 
             if widget.properties().invalidated {
                 widget.draw(&mut Context { canvas, textures }, target)?;
@@ -304,17 +784,29 @@ impl<'tc, W: Widget> Widgets<'tc, W> {
                 }
             }
 
-            // Copy the texture to the canvas.
-            let dst = Rect::new(x, y, width, height);
-            canvas.copy(target, None, dst).map_err(Error::msg)?;
+            // The texture is blended with the widgets below it if it is translucent.
+            let opacity = widget.opacity();
+            if opacity < 255 {
+                target.set_blend_mode(BlendMode::Blend);
+            } else {
+                target.set_blend_mode(BlendMode::None);
+            }
+            target.set_alpha_mod(opacity);
+            draw_order.push((id, rect));
+        }
 
-            // Mark the drawn widget as up-to-date.
+        // Mark the drawn widget as up-to-date. Hidden widgets are up-to-date once they have been
+        // erased from the canvas, and are redrawn as soon as they are shown again. Widgets outside
+        // of the viewport are drawn once they come into view.
+        if widget.is_hidden() || is_on_screen {
             widget.properties_mut().invalidated = false;
         }
 
+        // Children may lie outside of their parent, so they are checked against the viewport too.
+        let margin = virtualized.or(margin);
         for child_id in self.get_children_of(id).to_vec() {
             if child_id != id {
-                self.draw_widget(child_id, canvas)?;
+                self.draw_widget(child_id, canvas, viewport, margin, draw_order)?;
             }
         }
 
@@ -324,15 +816,22 @@ impl<'tc, W: Widget> Widgets<'tc, W> {
 
 /// A unique ID referring to a widget stored in a [`Widgets`] cache.
 ///
+/// Once the widget has been removed, the ID becomes stale and never refers to another widget.
 /// See the [`Widgets`] documentation for more info.
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
-pub struct WidgetId(u32);
+pub struct WidgetId {
+    index: u32,
+    generation: u32,
+}
 
 impl WidgetId {
     /// Returns the unique ID of the root widget.
     #[inline]
     pub fn root() -> Self {
-        WidgetId(0)
+        WidgetId {
+            index: 0,
+            generation: 0,
+        }
     }
 }
 
@@ -343,16 +842,42 @@ struct CacheEntry<'tc, W> {
     texture: WidgetTexture<'tc>,
     parent: WidgetId,
     children: Vec<WidgetId>,
+    /// Index of the layer to which the widget belongs.
+    layer: usize,
+    /// Region of the window in which the widget was drawn on the last frame, if any.
+    drawn: Option<Rect>,
+    /// Margin around the viewport within which descendants keep their textures, if virtualized.
+    virtualized: Option<u32>,
 }
 
 impl<'tc, W> CacheEntry<'tc, W> {
-    fn new(widget: W, parent: WidgetId) -> Self {
+    fn new(widget: W, parent: WidgetId, layer: usize) -> Self {
         CacheEntry {
             widget: RefCell::new(widget),
             texture: WidgetTexture::default(),
             parent,
             children: Vec::new(),
+            layer,
+            drawn: None,
+            virtualized: None,
+        }
+    }
+}
+
+impl<'tc, W: Widget> CacheEntry<'tc, W> {
+    /// Returns the regions of the window which must be redrawn because of this widget, i.e.
+    /// where it was drawn on the last frame and where it is to be drawn on the next frame.
+    ///
+    /// Both are `None` unless the widget has been invalidated.
+    fn damage(&self, viewport: Rect, is_layer_visible: bool) -> (Option<Rect>, Option<Rect>) {
+        let widget = self.widget.borrow();
+        if !is_layer_visible || !widget.properties().invalidated {
+            return (None, None);
         }
+
+        let rect = widget_rect(&*widget);
+        let is_shown = !widget.is_hidden() && overlaps(rect, viewport);
+        (self.drawn, Some(rect).filter(|_| is_shown))
     }
 }
 
@@ -372,16 +897,22 @@ impl<'tc> WidgetTexture<'tc> {
     ///
     /// Otherwise, a new texture which matches the given dimensions will be created using the given
     /// [`TextureCreator`](sdl2::render::TextureCreator).
+    ///
+    /// Textures of the right dimensions are taken from the `recycled` pool before creating any.
     fn create_or_resize(
         &mut self,
         tc: &'tc TextureCreator<WindowContext>,
+        recycled: &mut TexturePool<'tc>,
         width: u32,
         height: u32,
     ) -> anyhow::Result<&mut Texture<'tc>> {
-        if self.texture.is_none() || self.width != width || self.height != height {
+        if !self.matches(width, height) {
             self.width = width;
             self.height = height;
-            self.texture = tc.create_texture_target(None, width, height).map(Some)?;
+            self.texture = match recycled.take(width, height) {
+                Some(texture) => Some(texture),
+                None => tc.create_texture_target(None, width, height).map(Some)?,
+            };
         }
 
         match self.texture.as_mut() {
@@ -391,6 +922,27 @@ impl<'tc> WidgetTexture<'tc> {
     }
 }
 
+impl<'tc> WidgetTexture<'tc> {
+    /// Returns `true` if the texture exists and matches the given dimensions.
+    #[inline]
+    fn matches(&self, width: u32, height: u32) -> bool {
+        self.texture.is_some() && self.width == width && self.height == height
+    }
+
+    /// Moves the texture into the `recycled` pool, so that it can be reused by another widget.
+    ///
+    /// Returns `false` if there was no texture to release.
+    fn release(&mut self, recycled: &mut TexturePool<'tc>) -> bool {
+        match self.texture.take() {
+            Some(texture) => {
+                recycled.put(self.width, self.height, texture);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 impl<'tc> Debug for WidgetTexture<'tc> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct(stringify!(WidgetTexture))
@@ -400,22 +952,155 @@ impl<'tc> Debug for WidgetTexture<'tc> {
     }
 }
 
+/// A pool of unused render target textures, grouped by their dimensions.
+#[derive(Default)]
+struct TexturePool<'tc> {
+    textures: HashMap<(u32, u32), Vec<Texture<'tc>>>,
+}
+
+impl<'tc> TexturePool<'tc> {
+    /// At most this many textures of the same dimensions are kept around. Any further textures are
+    /// destroyed right away.
+    const MAX_TEXTURES_PER_SIZE: usize = 16;
+
+    /// Takes a texture with the given dimensions out of the pool, if there is one.
+    fn take(&mut self, width: u32, height: u32) -> Option<Texture<'tc>> {
+        self.textures.get_mut(&(width, height))?.pop()
+    }
+
+    /// Puts a texture with the given dimensions into the pool.
+    fn put(&mut self, width: u32, height: u32, texture: Texture<'tc>) {
+        let textures = self.textures.entry((width, height)).or_default();
+        if textures.len() < Self::MAX_TEXTURES_PER_SIZE {
+            textures.push(texture);
+        }
+    }
+}
+
+/// Returns the rectangle covered by a widget, in absolute coordinates.
+#[inline]
+fn widget_rect<W: Widget>(widget: &W) -> Rect {
+    let (x, y) = widget.origin();
+    let (width, height) = widget.bounds();
+    Rect::new(x, y, width, height)
+}
+
+/// Returns `true` if the two rectangles share at least one pixel.
+#[inline]
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bottom() && b.top() < a.bottom()
+}
+
+/// Redraws the damaged regions of the render target by copying every widget texture which overlaps
+/// them, in order from bottom to top.
+fn composite<W>(
+    target: &mut Canvas<Window>,
+    damage: &[Rect],
+    draw_order: &[(WidgetId, Rect)],
+    cache: &Slots<CacheEntry<W>>,
+) -> Result<(), String> {
+    for &region in damage {
+        // Pixels outside of the damaged region are left untouched.
+        target.set_clip_rect(region);
+        target.set_draw_color(Color::WHITE);
+        target.fill_rect(region)?;
+
+        let widgets = draw_order.iter().filter(|(_, dst)| overlaps(*dst, region));
+        for &(id, dst) in widgets {
+            if let Some(texture) = cache.get(id).and_then(|e| e.texture.texture.as_ref()) {
+                target.copy(texture, None, dst)?;
+            }
+        }
+    }
+
+    target.set_clip_rect(None);
+    Ok(())
+}
+
+/// Returns the smallest rectangle containing both rectangles.
+fn union(a: Rect, b: Rect) -> Rect {
+    let (left, top) = (a.left().min(b.left()), a.top().min(b.top()));
+    let (right, bottom) = (a.right().max(b.right()), a.bottom().max(b.bottom()));
+    Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+}
+
+/// Returns the region shared by both rectangles, if any.
+fn intersect(a: Rect, b: Rect) -> Option<Rect> {
+    let (left, top) = (a.left().max(b.left()), a.top().max(b.top()));
+    let (right, bottom) = (a.right().min(b.right()), a.bottom().min(b.bottom()));
+    if left < right && top < bottom {
+        Some(Rect::new(
+            left,
+            top,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Clips the damaged regions to the viewport and merges overlapping regions, so that no pixel is
+/// composited twice.
+///
+/// If many regions are left over, they are merged into a single region instead, since compositing
+/// many small regions is more expensive than compositing one larger region.
+fn merge_damage(damage: &mut Vec<Rect>, viewport: Rect) {
+    /// Damaged regions are merged into one once there are more than this many.
+    const MAX_DAMAGE_REGIONS: usize = 8;
+
+    let mut merged: Vec<Rect> = Vec::new();
+    for mut region in damage.drain(..).filter_map(|r| intersect(r, viewport)) {
+        // A grown region may overlap regions which were checked before, so check them all again.
+        while let Some(i) = merged.iter().position(|&r| overlaps(r, region)) {
+            region = union(region, merged.swap_remove(i));
+        }
+
+        merged.push(region);
+    }
+
+    if merged.len() > MAX_DAMAGE_REGIONS {
+        let all = merged.iter().copied().fold(merged[0], union);
+        merged.clear();
+        merged.push(all);
+    }
+
+    damage.extend(merged);
+}
+
+/// Returns `true` if `rect` lies within `margin` pixels of `viewport`.
+fn is_near(rect: Rect, viewport: Rect, margin: u32) -> bool {
+    let near = Rect::new(
+        viewport.x().saturating_sub(margin as i32),
+        viewport.y().saturating_sub(margin as i32),
+        viewport.width().saturating_add(margin * 2),
+        viewport.height().saturating_add(margin * 2),
+    );
+
+    overlaps(rect, near)
+}
+
 /// A shared mechanism for caching textures.
 ///
 /// This struct is accessible from the shared [`Context`] passed to every [`Widget::draw()`] call.
 pub struct Textures<'tc> {
     creator: &'tc TextureCreator<WindowContext>,
     cache: HashMap<PathBuf, Texture<'tc>>,
-    ttf_ctx: Sdl2TtfContext,
+    ttf_ctx: &'tc Sdl2TtfContext,
+    fonts: Vec<CachedFont<'tc>>,
 }
 
 impl<'tc> Textures<'tc> {
-    pub(crate) fn new(creator: &'tc TextureCreator<WindowContext>) -> anyhow::Result<Self> {
-        Ok(Textures {
+    pub(crate) fn new(
+        creator: &'tc TextureCreator<WindowContext>,
+        ttf_ctx: &'tc Sdl2TtfContext,
+    ) -> Self {
+        Textures {
             creator,
             cache: HashMap::default(),
-            ttf_ctx: sdl2::ttf::init()?,
-        })
+            ttf_ctx,
+            fonts: Vec::new(),
+        }
     }
 
     /// Returns a [`Texture`](sdl2::render::Texture) from an image file, caching it in memory.
@@ -435,46 +1120,80 @@ impl<'tc> Textures<'tc> {
         }
     }
 
-    /// Renders some text using a TTF font loaded from `path`, caching the font file in memory.
+    /// Lays out some text using a TTF font loaded from `path`, wrapping it to fit within the width
+    /// of `props.bounds` and tinting it with `props.color`. The result can then be drawn with
+    /// [`Textures::draw_text()`].
+    ///
+    /// Fonts are cached in memory for every combination of `path`, `point_size` and `style`, and
+    /// every glyph is only rasterized once into a glyph atlas, from which the text is assembled.
     ///
     /// Returns `Err` if the font file could not be found at the destination `path`, or if SDL was
-    /// unable to load the font successfully.
-    pub fn render_text<P>(
+    /// unable to load the font or rasterize its glyphs successfully.
+    pub fn layout_text<P>(
         &mut self,
         path: P,
         point_size: u16,
         style: FontStyle,
         props: &Properties,
         message: &str,
-    ) -> anyhow::Result<Text<'tc>>
+    ) -> anyhow::Result<Text>
     where
         P: AsRef<Path>,
     {
-        use sdl2::render::TextureQuery;
+        let index = self.font(path.as_ref(), point_size, style)?;
+        let (max_width, _) = props.bounds;
+        self.fonts[index].layout(self.creator, index, props.color, message, max_width)
+    }
 
-        let mut font = self
+    /// Returns the width and height of some text as laid out by [`Textures::layout_text()`], if
+    /// it were wrapped to fit within `max_width`, without rendering it.
+    ///
+    /// This is useful for sizing widgets to fit their text ahead of time. Returns `Err` if the
+    /// font could not be loaded.
+    pub fn measure_text<P>(
+        &mut self,
+        path: P,
+        point_size: u16,
+        style: FontStyle,
+        max_width: u32,
+        message: &str,
+    ) -> anyhow::Result<(u32, u32)>
+    where
+        P: AsRef<Path>,
+    {
+        let index = self.font(path.as_ref(), point_size, style)?;
+        Ok(self.fonts[index].measure(message, max_width))
+    }
+
+    /// Draws some text laid out by [`Textures::layout_text()`] onto the canvas, with its top-left
+    /// corner at (X, Y).
+    pub fn draw_text(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        text: &Text,
+        x: i32,
+        y: i32,
+    ) -> anyhow::Result<()> {
+        self.fonts[text.font].draw(canvas, text, x, y)
+    }
+
+    /// Returns the index of the cached font matching `path`, `point_size` and `style`, loading it
+    /// if necessary.
+    fn font(&mut self, path: &Path, point_size: u16, style: FontStyle) -> anyhow::Result<usize> {
+        // Only a handful of fonts are ever loaded, so a linear search is plenty fast.
+        let mut cached = self.fonts.iter();
+        if let Some(index) = cached.position(|font| font.matches(path, point_size, style)) {
+            return Ok(index);
+        }
+
+        let font = self
             .ttf_ctx
-            .load_font(path.as_ref(), point_size)
+            .load_font(path, point_size)
             .map_err(Error::msg)?;
 
-        font.set_style(style);
-
-        let (max_width, _) = props.bounds;
-        let surface = font
-            .render(message)
-            .blended_wrapped(props.color, max_width)?;
-
-        // NOTE: This is potentially expensive to do every frame. Ideally, we should have some form
-        // of caching system which renders and caches glyphs individually for every font, and then
-        // assembles finished textures from these cached glyphs. This is much too complex for this
-        // toy project, though.
-        let texture = self.creator.create_texture_from_surface(&surface)?;
-        let TextureQuery { width, height, .. } = texture.query();
-
-        Ok(Text {
-            texture,
-            bounds: (width, height),
-        })
+        self.fonts
+            .push(CachedFont::new(font, path, point_size, style));
+        Ok(self.fonts.len() - 1)
     }
 }
 
@@ -482,24 +1201,54 @@ impl<'tc> Debug for Textures<'tc> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct(stringify!(Textures))
             .field("cache", &self.cache.keys())
+            .field("fonts", &self.fonts.len())
             .finish()
     }
 }
 
-/// Contains some rendered text.
-///
-/// This struct is created by [`Textures::render_text()`]. See its documentation for more.
-pub struct Text<'tc> {
-    /// Texture containing the rendered text.
-    pub texture: Texture<'tc>,
-    /// Width and height of the texture, in pixels.
-    pub bounds: (u32, u32),
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_distance_to_viewport() {
+        let viewport = Rect::new(0, 0, 1920, 1080);
+        let on_screen = Rect::new(1800, 500, 500, 281);
+        let next_tile = Rect::new(1950, 500, 500, 281);
+        let far_away = Rect::new(2500, 500, 500, 281);
+
+        assert!(overlaps(on_screen, viewport));
+        assert!(!overlaps(next_tile, viewport));
+        assert!(is_near(next_tile, viewport, 528));
+        assert!(!is_near(far_away, viewport, 528));
+        assert!(is_near(Rect::new(-1000, 0, 500, 281), viewport, 528));
+    }
 
-impl<'tc> Debug for Text<'tc> {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.debug_struct(stringify!(Text))
-            .field("bounds", &self.bounds)
-            .finish()
+    #[test]
+    fn merges_damaged_regions() {
+        let viewport = Rect::new(0, 0, 1920, 1080);
+        let mut damage = vec![
+            Rect::new(100, 100, 500, 281),
+            Rect::new(2000, 100, 500, 281),
+            Rect::new(1800, 600, 500, 281),
+            Rect::new(400, 300, 500, 281),
+            Rect::new(0, 0, 50, 50),
+        ];
+
+        // Regions off the screen are dropped, and the overlapping ones are merged.
+        merge_damage(&mut damage, viewport);
+        damage.sort_by_key(|r| (r.x(), r.y()));
+        assert_eq!(
+            damage,
+            [
+                Rect::new(0, 0, 50, 50),
+                Rect::new(100, 100, 800, 481),
+                Rect::new(1800, 600, 120, 281),
+            ]
+        );
+
+        let mut damage: Vec<_> = (0..10).map(|i| Rect::new(i * 100, 0, 50, 50)).collect();
+        merge_damage(&mut damage, viewport);
+        assert_eq!(damage, [Rect::new(0, 0, 950, 50)]);
     }
 }
@@ -0,0 +1,150 @@
+//! Generational storage for the entries of a [`Widgets`](super::Widgets) cache.
+
+use super::WidgetId;
+
+/// A vector of reusable slots, addressed by generational [`WidgetId`]s.
+///
+/// Every slot has a generation counter which is incremented whenever its value is removed. An ID
+/// only refers to the value which was inserted together with it, so IDs of removed values are
+/// detected as stale instead of referring to whichever value reuses the slot afterwards.
+#[derive(Debug)]
+pub(super) struct Slots<T> {
+    slots: Vec<Slot<T>>,
+    /// Indices of the vacant slots, which are reused before any new slots are allocated.
+    free: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<T> Slots<T> {
+    /// Creates a new empty `Slots` vector.
+    #[inline]
+    pub fn new() -> Self {
+        Slots {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Stores a value in a vacant slot, and returns the unique ID referring to it.
+    pub fn insert(&mut self, value: T) -> WidgetId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                WidgetId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                let index = self.slots.len() as u32;
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                WidgetId {
+                    index,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Returns an immutable reference to a value, or `None` if `id` is stale.
+    #[inline]
+    pub fn get(&self, id: WidgetId) -> Option<&T> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    /// Returns a mutable reference to a value, or `None` if `id` is stale.
+    #[inline]
+    pub fn get_mut(&mut self, id: WidgetId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Removes a value, invalidating its ID, and returns it. Returns `None` if `id` is stale.
+    pub fn remove(&mut self, id: WidgetId) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        Some(value)
+    }
+
+    /// Removes every value for which `f` returns `false`, invalidating their IDs.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if matches!(slot.value, Some(ref value) if !f(value)) {
+                slot.value = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+    }
+
+    /// Returns an iterator over immutable references to all values, in no particular order.
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    /// Returns an iterator over mutable references to all values, in no particular order.
+    #[inline]
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_stale_ids() {
+        let mut slots = Slots::new();
+        let first = slots.insert("first");
+        let second = slots.insert("second");
+        assert_eq!(first, WidgetId::root());
+
+        assert_eq!(slots.remove(second), Some("second"));
+        assert_eq!(slots.get(second), None);
+        assert_eq!(slots.remove(second), None);
+
+        // The vacant slot is reused, but the stale ID does not refer to the new value.
+        let third = slots.insert("third");
+        assert_eq!(third.index, second.index);
+        assert_ne!(third, second);
+        assert_eq!(slots.get(second), None);
+        assert_eq!(slots.get(third), Some(&"third"));
+        assert_eq!(slots.get(first), Some(&"first"));
+    }
+
+    #[test]
+    fn retains_matching_values() {
+        let mut slots = Slots::new();
+        let ids: Vec<_> = (0..4).map(|i| slots.insert(i)).collect();
+
+        slots.retain(|value| value % 2 == 0);
+        assert_eq!(slots.values().copied().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(slots.get(ids[1]), None);
+        assert_eq!(slots.get(ids[2]), Some(&2));
+
+        *slots.get_mut(ids[2]).unwrap() += 10;
+        assert_eq!(slots.values_mut().map(|v| *v).collect::<Vec<_>>(), [0, 12]);
+    }
+}
@@ -0,0 +1,487 @@
+//! Font cache and glyph atlases for rendering text.
+//!
+//! Rather than rasterizing every string from scratch, every glyph is rasterized only once per font
+//! and stored in an atlas texture, from which strings are then assembled, a la
+//! [SDL_FontCache](https://github.com/grimfang4/SDL_FontCache).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use fnv::FnvHashMap as HashMap;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::ttf::{Font, FontStyle, GlyphMetrics};
+use sdl2::video::{Window, WindowContext};
+
+/// Width and height of every atlas page, in pixels.
+const ATLAS_SIZE: u32 = 1024;
+/// Empty space left between the glyphs in an atlas page, so that they never bleed into each other.
+const GLYPH_PADDING: u32 = 1;
+
+/// A font loaded at a specific point size and style, along with the atlas of the glyphs which have
+/// been rasterized from it so far.
+pub(super) struct CachedFont<'tc> {
+    path: PathBuf,
+    point_size: u16,
+    style: FontStyle,
+    font: Font<'tc, 'static>,
+    /// Atlas textures into which glyphs are rasterized. A new page is added once the last one is
+    /// full.
+    pages: Vec<Texture<'tc>>,
+    packer: ShelfPacker,
+    metrics: HashMap<char, Option<Metrics>>,
+    glyphs: HashMap<char, Option<AtlasGlyph>>,
+    kerning: HashMap<(char, char), i32>,
+}
+
+impl<'tc> CachedFont<'tc> {
+    /// Wraps a newly loaded font, applying the given style to it.
+    pub fn new(
+        mut font: Font<'tc, 'static>,
+        path: &Path,
+        point_size: u16,
+        style: FontStyle,
+    ) -> Self {
+        font.set_style(style);
+        font.set_kerning(true);
+
+        CachedFont {
+            path: path.to_owned(),
+            point_size,
+            style,
+            font,
+            pages: Vec::new(),
+            packer: ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE),
+            metrics: HashMap::default(),
+            glyphs: HashMap::default(),
+            kerning: HashMap::default(),
+        }
+    }
+
+    /// Returns `true` if this font was loaded from `path` with the given point size and style.
+    #[inline]
+    pub fn matches(&self, path: &Path, point_size: u16, style: FontStyle) -> bool {
+        self.point_size == point_size && self.style == style && self.path == path
+    }
+
+    /// Returns the width and height of `message` once laid out with [`CachedFont::layout()`],
+    /// without rasterizing any glyphs.
+    pub fn measure(&mut self, message: &str, max_width: u32) -> (u32, u32) {
+        let (_, width, lines) = layout(message, max_width, |prev, ch| self.advance(prev, ch));
+        (width, self.text_height(lines))
+    }
+
+    /// Lays out `message` in the given color, wrapping lines at spaces so that they fit within
+    /// `max_width` if possible, and rasterizes any glyphs missing from the atlas.
+    ///
+    /// `font` is the index of this font in the cache, which is used to draw the text later on.
+    pub fn layout(
+        &mut self,
+        creator: &'tc TextureCreator<WindowContext>,
+        font: usize,
+        color: Color,
+        message: &str,
+        max_width: u32,
+    ) -> anyhow::Result<Text> {
+        let (positions, width, lines) =
+            layout(message, max_width, |prev, ch| self.advance(prev, ch));
+
+        let line_skip = self.font.recommended_line_spacing();
+        let mut glyphs = Vec::with_capacity(positions.len());
+        for (ch, x, line) in positions {
+            if let Some(glyph) = self.glyph(creator, ch)? {
+                let (width, height) = glyph.src.size();
+                let y = line as i32 * line_skip;
+                let dst = Rect::new(x + glyph.offset_x, y, width, height);
+                glyphs.push((glyph.page, glyph.src, dst));
+            }
+        }
+
+        Ok(Text {
+            bounds: (width, self.text_height(lines)),
+            font,
+            color,
+            glyphs,
+        })
+    }
+
+    /// Copies the glyphs of some text laid out with this font onto the canvas at (X, Y).
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        text: &Text,
+        x: i32,
+        y: i32,
+    ) -> anyhow::Result<()> {
+        // Glyphs are rasterized in white, so that they can be tinted in any color.
+        let Color { r, g, b, a } = text.color;
+        for page in &mut self.pages {
+            page.set_color_mod(r, g, b);
+            page.set_alpha_mod(a);
+        }
+
+        for &(page, src, dst) in &text.glyphs {
+            let dst = Rect::new(dst.x() + x, dst.y() + y, dst.width(), dst.height());
+            canvas
+                .copy(&self.pages[page], src, dst)
+                .map_err(Error::msg)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the height of text spanning the given number of lines, in pixels.
+    fn text_height(&self, lines: usize) -> u32 {
+        let line_skip = self.font.recommended_line_spacing();
+        let height = (lines.max(1) - 1) as i32 * line_skip + self.font.height();
+        height.max(0) as u32
+    }
+
+    /// Returns the kerning between `prev` and `ch`, and the advance of `ch`.
+    fn advance(&mut self, prev: Option<char>, ch: char) -> (i32, i32) {
+        let kerning = prev.map_or(0, |prev| self.kerning(prev, ch));
+        let advance = self.metrics(ch).map_or(0, |m| m.advance);
+        (kerning, advance)
+    }
+
+    /// Returns the metrics of the glyph for `ch`, or `None` if the font does not provide one.
+    fn metrics(&mut self, ch: char) -> Option<Metrics> {
+        let font = &self.font;
+        *self
+            .metrics
+            .entry(ch)
+            .or_insert_with(|| font.find_glyph_metrics(ch).map(|m| Metrics::from(&m)))
+    }
+
+    /// Returns the kerning between the glyphs for `prev` and `ch`, in pixels.
+    ///
+    /// SDL_ttf does not expose kerning directly, so it is derived from the width of the pair as
+    /// measured by SDL_ttf, compared to the width of the pair without any kerning applied.
+    fn kerning(&mut self, prev: char, ch: char) -> i32 {
+        if let Some(&kerning) = self.kerning.get(&(prev, ch)) {
+            return kerning;
+        }
+
+        let mut pair = [0; 8];
+        let len = prev.encode_utf8(&mut pair).len();
+        let len = len + ch.encode_utf8(&mut pair[len..]).len();
+        let pair = std::str::from_utf8(&pair[..len]).expect("pair is valid UTF-8");
+
+        let kerning = match (
+            self.metrics(prev),
+            self.metrics(ch),
+            self.font.size_of(pair),
+        ) {
+            (Some(a), Some(b), Ok((width, _))) => width as i32 - unkerned_width(a, b),
+            _ => 0,
+        };
+
+        self.kerning.insert((prev, ch), kerning);
+        kerning
+    }
+
+    /// Returns the glyph for `ch` from the atlas, rasterizing it if necessary.
+    ///
+    /// Returns `None` if the glyph is blank, e.g. a space, or if the font does not provide one.
+    fn glyph(
+        &mut self,
+        creator: &'tc TextureCreator<WindowContext>,
+        ch: char,
+    ) -> anyhow::Result<Option<AtlasGlyph>> {
+        if let Some(&glyph) = self.glyphs.get(&ch) {
+            return Ok(glyph);
+        }
+
+        let glyph = match self.metrics(ch) {
+            Some(metrics) if !ch.is_whitespace() => Some(self.rasterize(creator, ch, metrics)?),
+            _ => None,
+        };
+
+        self.glyphs.insert(ch, glyph);
+        Ok(glyph)
+    }
+
+    /// Renders the glyph for `ch` and copies it into the atlas.
+    fn rasterize(
+        &mut self,
+        creator: &'tc TextureCreator<WindowContext>,
+        ch: char,
+        metrics: Metrics,
+    ) -> anyhow::Result<AtlasGlyph> {
+        let surface = self.font.render_char(ch).blended(Color::WHITE)?;
+        let surface = surface
+            .convert_format(PixelFormatEnum::ARGB8888)
+            .map_err(Error::msg)?;
+
+        let (width, height) = surface.size();
+        let padded = (width + GLYPH_PADDING, height + GLYPH_PADDING);
+        let position = if self.pages.is_empty() {
+            None
+        } else {
+            self.packer.pack(padded.0, padded.1)
+        };
+
+        let (x, y) = match position {
+            Some(position) => position,
+            None => {
+                // The last page is full, so start a new one.
+                let mut page = creator.create_texture_static(
+                    PixelFormatEnum::ARGB8888,
+                    ATLAS_SIZE,
+                    ATLAS_SIZE,
+                )?;
+                page.set_blend_mode(BlendMode::Blend);
+                self.pages.push(page);
+
+                self.packer = ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE);
+                self.packer
+                    .pack(padded.0, padded.1)
+                    .ok_or_else(|| anyhow!("glyph {:?} is too large for the atlas", ch))?
+            }
+        };
+
+        let src = Rect::new(x, y, width, height);
+        let page = self.pages.len() - 1;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock(|pixels| self.pages[page].update(src, pixels, pitch))?;
+
+        // The rasterized glyph extends to the left of the pen position if it has a negative
+        // bearing.
+        Ok(AtlasGlyph {
+            page,
+            src,
+            offset_x: metrics.minx.min(0),
+        })
+    }
+}
+
+/// Horizontal metrics of a glyph, in pixels.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Metrics {
+    minx: i32,
+    maxx: i32,
+    advance: i32,
+}
+
+impl From<&GlyphMetrics> for Metrics {
+    #[inline]
+    fn from(m: &GlyphMetrics) -> Self {
+        Metrics {
+            minx: m.minx,
+            maxx: m.maxx,
+            advance: m.advance,
+        }
+    }
+}
+
+/// A glyph rasterized into an atlas page.
+#[derive(Clone, Copy, Debug)]
+struct AtlasGlyph {
+    /// Index of the atlas page containing the glyph.
+    page: usize,
+    /// Location of the glyph in its atlas page.
+    src: Rect,
+    /// Horizontal distance from the pen position to the left edge of the rasterized glyph.
+    offset_x: i32,
+}
+
+/// Some text laid out with a cached font, ready to be drawn.
+///
+/// This struct is created by [`Textures::layout_text()`](super::Textures::layout_text()). See its
+/// documentation for more.
+#[derive(Debug)]
+pub struct Text {
+    /// Width and height of the text, in pixels.
+    pub bounds: (u32, u32),
+    /// Index of the font in the cache.
+    pub(super) font: usize,
+    color: Color,
+    /// Atlas page, location in the atlas, and location relative to the text of every glyph.
+    glyphs: Vec<(usize, Rect, Rect)>,
+}
+
+/// Packs rectangles into a fixed-size area, from left to right in rows called shelves.
+///
+/// Glyphs of the same font are similar in height, so very little space is wasted.
+#[derive(Debug)]
+struct ShelfPacker {
+    width: u32,
+    height: u32,
+    /// Position of the next rectangle on the current shelf.
+    x: u32,
+    y: u32,
+    /// Height of the tallest rectangle on the current shelf.
+    shelf_height: u32,
+}
+
+impl ShelfPacker {
+    /// Creates a new empty `ShelfPacker` for an area of the given size.
+    #[inline]
+    fn new(width: u32, height: u32) -> Self {
+        ShelfPacker {
+            width,
+            height,
+            x: 0,
+            y: 0,
+            shelf_height: 0,
+        }
+    }
+
+    /// Reserves space for a rectangle of the given size, and returns its top-left corner.
+    ///
+    /// Returns `None` if there is not enough space left.
+    fn pack(&mut self, width: u32, height: u32) -> Option<(i32, i32)> {
+        if self.x + width > self.width {
+            self.x = 0;
+            self.y += self.shelf_height;
+            self.shelf_height = 0;
+        }
+
+        if width > self.width || self.y + height > self.height {
+            return None;
+        }
+
+        let position = (self.x as i32, self.y as i32);
+        self.x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(position)
+    }
+}
+
+/// Positions every character of `message` relative to the left edge of the text, wrapping lines at
+/// spaces so that they fit within `max_width` if possible. Newlines always start a new line.
+///
+/// `advance(prev, ch)` returns the kerning between the characters `prev` and `ch`, and how far the
+/// pen moves past `ch`. Returns the character, X coordinate and line index of every character
+/// other than spaces, along with the width of the widest line and the number of lines.
+fn layout<F>(message: &str, max_width: u32, mut advance: F) -> (Vec<(char, i32, usize)>, u32, usize)
+where
+    F: FnMut(Option<char>, char) -> (i32, i32),
+{
+    let mut positions = Vec::with_capacity(message.len());
+    let mut width = 0;
+    let mut line = 0;
+
+    for (i, paragraph) in message.split('\n').enumerate() {
+        if i > 0 {
+            line += 1;
+        }
+
+        let (mut pen, mut prev) = (0, None);
+        for (j, word) in paragraph.split(' ').enumerate() {
+            let mut word_prev = None;
+            let word_width: i32 = word
+                .chars()
+                .map(|ch| {
+                    let (kerning, advance) = advance(word_prev.replace(ch), ch);
+                    kerning + advance
+                })
+                .sum();
+
+            if j > 0 {
+                // Wrap the word onto the next line if it does not fit. Words which are wider than
+                // an entire line are left to overflow it.
+                let (kerning, space) = advance(prev, ' ');
+                if pen > 0 && pen + kerning + space + word_width > max_width as i32 {
+                    line += 1;
+                    pen = 0;
+                    prev = None;
+                } else {
+                    pen += kerning + space;
+                    prev = Some(' ');
+                }
+            }
+
+            for ch in word.chars() {
+                let (kerning, advance) = advance(prev, ch);
+                pen += kerning;
+                positions.push((ch, pen, line));
+                pen += advance;
+                prev = Some(ch);
+            }
+
+            width = width.max(pen.max(0) as u32);
+        }
+    }
+
+    (positions, width, line + 1)
+}
+
+/// Returns the width of the glyph `a` followed by the glyph `b` without any kerning, as measured
+/// by SDL_ttf, i.e. from the leftmost to the rightmost pixel or pen position.
+fn unkerned_width(a: Metrics, b: Metrics) -> i32 {
+    let left = a.minx.min(a.advance + b.minx).min(0);
+    let right = a.maxx.max(a.advance).max(a.advance + b.maxx.max(b.advance));
+    right - left
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is 10 pixels wide, and "AV" is kerned by -2 pixels.
+    fn monospace(prev: Option<char>, ch: char) -> (i32, i32) {
+        let kerning = if prev == Some('A') && ch == 'V' {
+            -2
+        } else {
+            0
+        };
+        (kerning, 10)
+    }
+
+    #[test]
+    fn lays_out_text_with_kerning_and_wrapping() {
+        let (positions, width, lines) = layout("AV is\nok", 1000, monospace);
+        assert_eq!(
+            positions,
+            [
+                ('A', 0, 0),
+                ('V', 8, 0),
+                ('i', 28, 0),
+                ('s', 38, 0),
+                ('o', 0, 1),
+                ('k', 10, 1)
+            ]
+        );
+        assert_eq!((width, lines), (48, 2));
+
+        // "lorem ipsum" is 110 pixels wide, so "ipsum" wraps onto the next line.
+        let (positions, width, lines) = layout("lorem ipsum", 100, monospace);
+        assert_eq!(positions[5], ('i', 0, 1));
+        assert_eq!((width, lines), (50, 2));
+
+        // A single word which is wider than a line is not wrapped.
+        let (_, width, lines) = layout("supercalifragilistic", 100, monospace);
+        assert_eq!((width, lines), (200, 1));
+    }
+
+    #[test]
+    fn packs_glyphs_into_shelves() {
+        let mut packer = ShelfPacker::new(100, 50);
+        assert_eq!(packer.pack(40, 20), Some((0, 0)));
+        assert_eq!(packer.pack(40, 25), Some((40, 0)));
+        assert_eq!(packer.pack(40, 20), Some((0, 25)));
+        assert_eq!(packer.pack(40, 30), None);
+        assert_eq!(packer.pack(200, 1), None);
+    }
+
+    #[test]
+    fn derives_kerning_from_pair_width() {
+        let a = Metrics {
+            minx: 1,
+            maxx: 20,
+            advance: 22,
+        };
+        let v = Metrics {
+            minx: -1,
+            maxx: 21,
+            advance: 21,
+        };
+
+        // Without kerning, the pair spans from the leftmost pixel to the right edge of the second
+        // glyph, whichever glyph comes first.
+        assert_eq!(unkerned_width(a, v), 43);
+        assert_eq!(unkerned_width(v, a), 44);
+    }
+}
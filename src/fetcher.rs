@@ -1,43 +1,215 @@
 //! Background HTTP resource fetching.
 
+pub use self::config::FetcherConfig;
+pub use self::error::FetchError;
+pub use self::progress::Progress;
+pub use self::retry::{is_transient, RetryPolicy};
+pub use self::scheduler::Priority;
+pub use self::timeout::Timeouts;
+pub use self::transport::{
+    FileTransport, HttpTransport, MemoryTransport, Transport, TransportResponse, TransportResult,
+};
+
+use std::any::Any;
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
 use std::task::Poll;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
+use bytes::Bytes;
 use flume::{Receiver, Sender};
-use fnv::FnvHashMap as HashMap;
-use futures_util::future::{AbortHandle, AbortRegistration, Abortable};
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use futures_util::future::{AbortHandle, AbortRegistration, Abortable, LocalBoxFuture};
 use futures_util::StreamExt;
-use reqwest::Client;
-use reqwest::RequestBuilder;
-use tempfile::TempPath;
+use reqwest::header::{
+    HeaderMap, HeaderName, ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use url::Url;
 
+use self::cache::{Cache, CachedFile, DiskCache, Download, Metadata, Partial, Status};
+use self::memory::{BytesResponse, MemoryCache};
+use self::scheduler::Scheduler;
+use self::timeout::with_timeouts;
+
+mod cache;
+mod config;
+mod error;
+mod memory;
+mod progress;
+mod retry;
+mod scheduler;
+mod timeout;
+mod transport;
+
 const MAX_CHANNEL_CAP: usize = 1;
 
-/// A request sent from `Fetcher` to the background thread asking to download a file from a URL.
-type Request = Url;
+/// A request sent from `Fetcher` to the background thread.
+#[derive(Debug)]
+enum Request {
+    /// Asks to download a file from a URL, replying with [`Reply::Fetch`].
+    Fetch(Url),
+    /// Asks to download a file from a URL into memory, replying with [`Reply::FetchBytes`].
+    FetchBytes(Url),
+    /// Asks to download a file from a URL into memory and decode it, replying with
+    /// [`Reply::FetchJson`].
+    FetchJson(Url, Decoder),
+    /// Asks to download a file from a URL and send a [`Completion`] event once it has finished.
+    Subscribe(Url, Priority),
+    /// Asks to download a file from a URL into memory and send a [`Completion`] event containing
+    /// the decoded file once it has finished.
+    SubscribeJson(Url, Decoder, Priority),
+    /// Changes the priority of a pending download.
+    SetPriority(Url, Priority),
+    /// Overrides the timeouts for future requests for a URL.
+    SetTimeouts(Url, Timeouts),
+    /// Stops a pending download and forgets about its subscribers.
+    Cancel(Url),
+    /// Forgets a failed download, so that it starts over the next time it is requested.
+    ForgetFailure(Url),
+    /// Asks whether the cached copy of a URL has expired, replying with [`Reply::IsStale`].
+    IsStale(Url),
+    /// Asks when a failed download of a URL will be retried, replying with [`Reply::RetryAt`].
+    RetryAt(Url),
+}
+
+/// A reply sent from the background thread to `Fetcher` in response to a [`Request`].
+#[derive(Debug)]
+enum Reply {
+    /// Contains the current download status of the requested file.
+    Fetch(Response),
+    /// Contains the contents of the requested file, once it has been downloaded into memory.
+    FetchBytes(BytesResponse),
+    /// Contains the decoded contents of the requested file, once it has been downloaded.
+    FetchJson(Response<Box<dyn Any + Send + Sync>>),
+    /// Indicates whether the cached copy of the requested file has expired.
+    IsStale(bool),
+    /// Contains the time of the next download attempt, if the requested file is cooling down.
+    RetryAt(Option<Instant>),
+}
+
+/// The current download status of a file, as returned by [`Fetcher::poll_fetch()`].
+///
+/// Downloads into memory, as returned by [`Fetcher::poll_fetch_bytes()`] and
+/// [`Fetcher::poll_fetch_json()`], contain their contents rather than a location on disk.
+#[derive(Debug)]
+pub enum Response<T = PathBuf> {
+    /// The download is queued, in progress or cooling down in between attempts.
+    Pending(Progress),
+    /// The download has finished, containing either the location of the file on disk or the error
+    /// which caused the download to fail.
+    Ready(Result<T, FetchError>),
+}
+
+impl<T> Response<T> {
+    /// Returns `true` if the download has not finished yet.
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Response::Pending(_))
+    }
+
+    /// Returns the progress of the download, if it has not finished yet.
+    #[inline]
+    pub fn progress(&self) -> Option<&Progress> {
+        match self {
+            Response::Pending(progress) => Some(progress),
+            Response::Ready(_) => None,
+        }
+    }
+
+    /// Converts the response into a `Poll`, discarding the progress of a pending download.
+    #[inline]
+    pub fn into_poll(self) -> Poll<Result<T, FetchError>> {
+        match self {
+            Response::Pending(_) => Poll::Pending,
+            Response::Ready(result) => Poll::Ready(result),
+        }
+    }
+
+    /// Maps the result of a finished download with `f`, keeping the progress of a pending one.
+    fn and_then<U, F>(self, f: F) -> Response<U>
+    where
+        F: FnOnce(T) -> Result<U, FetchError>,
+    {
+        match self {
+            Response::Pending(progress) => Response::Pending(progress),
+            Response::Ready(result) => Response::Ready(result.and_then(f)),
+        }
+    }
+}
+
+/// Decodes the contents of a file into a type-erased value on the background thread.
+type Decoder = fn(Bytes) -> Result<Box<dyn Any + Send + Sync>, FetchError>;
 
-/// A response sent from the background thread to `Fetcher` containing the current download status.
-type Response = Poll<anyhow::Result<PathBuf>>;
+/// An event sent from the background thread once a subscribed download has finished.
+///
+/// See [`Fetcher::subscribe()`] and [`Fetcher::subscribe_json()`] for more details.
+#[derive(Debug)]
+pub struct Completion {
+    /// The URL which was subscribed to.
+    pub url: Url,
+    /// The downloaded file, or the error which caused the download to fail.
+    pub result: Result<Payload, FetchError>,
+}
+
+/// The contents of a subscribed download which has finished successfully.
+#[derive(Clone, Debug)]
+pub enum Payload {
+    /// The location of a file on disk, subscribed to with [`Fetcher::subscribe()`].
+    File(PathBuf),
+    /// A JSON document which was decoded on the background thread, subscribed to with
+    /// [`Fetcher::subscribe_json()`].
+    Json(Arc<dyn Any + Send + Sync>),
+}
+
+impl Payload {
+    /// Returns the location of the downloaded file on disk, if it was saved to disk.
+    #[inline]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Payload::File(path) => Some(path),
+            Payload::Json(_) => None,
+        }
+    }
+
+    /// Returns the decoded JSON document, if it was decoded into a `T`.
+    #[inline]
+    pub fn json<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        match self {
+            Payload::File(_) => None,
+            Payload::Json(value) => value.clone().downcast().ok(),
+        }
+    }
+}
 
 /// An in-memory cache of pending and completed downloads, keyed by their URLs.
-type DownloadCache = RefCell<HashMap<Url, Poll<anyhow::Result<TempPath>>>>;
+type DownloadCache = RefCell<Cache>;
 
-/// Downloads files via HTTP and caches them in the OS temporary directory.
+/// Downloads files via HTTP and caches them on disk.
 ///
 /// This utilizes a dedicated OS thread to prevent potentially blocking the main thread with I/O.
 /// Individual download requests are processed concurrently on this thread for maximum throughput.
 ///
+/// Files are cached in the OS temporary directory by default, unless a persistent cache directory
+/// was specified in the [`FetcherConfig`].
+///
 /// When `Fetcher` is dropped, the background thread will terminate, stopping any in-flight
-/// downloads and clearing all cached files from disk.
+/// downloads and clearing all temporary files from disk.
 #[derive(Debug)]
 pub struct Fetcher {
     request_tx: Sender<Request>,
-    response_rx: Receiver<Response>,
+    response_rx: Receiver<Reply>,
+    completion_rx: Receiver<Completion>,
     remote_task: AbortHandle,
     handle: Option<JoinHandle<()>>,
 }
@@ -52,11 +224,10 @@ impl Fetcher {
     /// Returns `Err` if the file at the target URL does not exist, an I/O error occurred, or the
     /// background worker thread was terminated.
     #[inline]
-    pub fn fetch(&self, url: Request) -> anyhow::Result<PathBuf> {
+    pub fn fetch(&self, url: Url) -> Result<PathBuf, FetchError> {
         loop {
-            match self.poll_fetch(url.clone()) {
-                Poll::Ready(result) => return result,
-                Poll::Pending => {}
+            if let Response::Ready(result) = self.poll_fetch(url.clone()) {
+                return result;
             }
         }
     }
@@ -67,12 +238,203 @@ impl Fetcher {
     /// the current status can be polled again by repeatedly calling this method. If the file from
     /// the requested URL already exists on disk, its path will be returned immediately.
     ///
-    /// Returns `Poll::Ready(Err(_))` if the file at the target URL does not exist, an I/O error
+    /// If a cached copy of the file exists but has expired, it is revalidated with the server
+    /// using a conditional request, and only downloaded again if it has actually changed.
+    ///
+    /// Pending responses report how much of the file has been received so far, which can be used
+    /// to render progress bars for large files.
+    ///
+    /// Returns `Response::Ready(Err(_))` if the file at the target URL does not exist, an I/O error
     /// occurred, or the background worker thread was terminated.
     #[inline]
-    pub fn poll_fetch(&self, url: Request) -> Response {
+    pub fn poll_fetch(&self, url: Url) -> Response {
+        match self.request(Request::Fetch(url)) {
+            Reply::Fetch(response) => response,
+            reply => unreachable!("unexpected reply to fetch request: {:?}", reply),
+        }
+    }
+
+    /// Downloads a file located at the given URL into memory and returns its contents.
+    ///
+    /// Unlike [`fetch()`](Fetcher::fetch()), the file is kept in memory rather than written to a
+    /// temporary file. It is only written to disk if a persistent cache directory was configured,
+    /// so that it survives restarts. Note: this method _blocks_ the main thread until the download
+    /// is complete. For a non-blocking version of this method, see
+    /// [`poll_fetch_bytes()`](Fetcher::poll_fetch_bytes()) instead.
+    #[inline]
+    pub fn fetch_bytes(&self, url: Url) -> Result<Bytes, FetchError> {
+        loop {
+            if let Response::Ready(result) = self.poll_fetch_bytes(url.clone()) {
+                return result;
+            }
+        }
+    }
+
+    /// Attempts to download a file located at the given URL into memory and return its contents.
+    ///
+    /// This method does _not_ block if the file is not ready. Like [`poll_fetch()`], downloads
+    /// into memory are subject to the concurrency limit, can be cancelled, report their progress
+    /// and are retried after cooling down. Files are kept in memory until they are evicted to make
+    /// room for others (see [`FetcherConfig::with_max_memory_size()`]), and are revalidated with
+    /// the server once they have expired.
+    ///
+    /// [`poll_fetch()`]: Fetcher::poll_fetch()
+    #[inline]
+    pub fn poll_fetch_bytes(&self, url: Url) -> Response<Bytes> {
+        match self.request(Request::FetchBytes(url)) {
+            Reply::FetchBytes(response) => response,
+            reply => unreachable!("unexpected reply to fetch bytes request: {:?}", reply),
+        }
+    }
+
+    /// Downloads a JSON document located at the given URL and deserializes it into a `T`.
+    ///
+    /// The document is downloaded into memory and deserialized on the background thread, so that
+    /// large documents never have to be parsed on the main thread. Note: this method _blocks_ the
+    /// main thread until the download is complete. For a non-blocking version of this method, see
+    /// [`poll_fetch_json()`](Fetcher::poll_fetch_json()) instead.
+    ///
+    /// Returns `Err(FetchError::Decode(_))` if the document is not valid JSON or does not match
+    /// the structure of `T`.
+    #[inline]
+    pub fn fetch_json<T>(&self, url: Url) -> Result<T, FetchError>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        loop {
+            if let Response::Ready(result) = self.poll_fetch_json(url.clone()) {
+                return result;
+            }
+        }
+    }
+
+    /// Attempts to download a JSON document located at the given URL and deserialize it into a
+    /// `T` on the background thread.
+    ///
+    /// This method does _not_ block if the document is not ready. The document is deserialized
+    /// again every time this method returns `Poll::Ready`.
+    #[inline]
+    pub fn poll_fetch_json<T>(&self, url: Url) -> Response<T>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        match self.request(Request::FetchJson(url, decode_json::<T>)) {
+            Reply::FetchJson(response) => response
+                .and_then(|value| Ok(*value.downcast().expect("decoded value has the wrong type"))),
+            reply => unreachable!("unexpected reply to fetch JSON request: {:?}", reply),
+        }
+    }
+
+    /// Returns `true` if the cached copy of the file at the given URL has expired.
+    ///
+    /// Stale files are revalidated with the server the next time they are fetched. Returns `false`
+    /// if the file is still fresh, or if it has never been downloaded at all. Files downloaded
+    /// into memory are taken into account as well.
+    #[inline]
+    pub fn is_stale(&self, url: Url) -> bool {
+        match self.request(Request::IsStale(url)) {
+            Reply::IsStale(is_stale) => is_stale,
+            reply => unreachable!("unexpected reply to staleness request: {:?}", reply),
+        }
+    }
+
+    /// Starts downloading a file from the given URL in the background, if it isn't already, and
+    /// sends a [`Completion`] event once the download has either finished or failed for good.
+    ///
+    /// This method does _not_ block. Rather than polling every pending download on every frame
+    /// with [`poll_fetch()`](Fetcher::poll_fetch()), callers should drain the events once per
+    /// frame using [`completions()`](Fetcher::completions()). If the file from the requested URL
+    /// already exists on disk, the event is sent immediately.
+    ///
+    /// Downloads with higher priorities are started first once the concurrency limit set in the
+    /// [`FetcherConfig`] is reached. If the download is already pending, its priority is updated.
+    #[inline]
+    pub fn subscribe(&self, url: Url, priority: Priority) {
+        self.notify(Request::Subscribe(url, priority));
+    }
+
+    /// Starts downloading a JSON document from the given URL into memory, if it isn't already, and
+    /// sends a [`Completion`] event containing the document deserialized into a `T` once the
+    /// download has either finished or failed for good.
+    ///
+    /// Like [`fetch_json()`](Fetcher::fetch_json()), the document is deserialized on the
+    /// background thread. The decoded document can be retrieved from the event with
+    /// [`Payload::json()`]. Apart from that, this method behaves just like
+    /// [`subscribe()`](Fetcher::subscribe()).
+    #[inline]
+    pub fn subscribe_json<T>(&self, url: Url, priority: Priority)
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.notify(Request::SubscribeJson(url, decode_json::<T>, priority));
+    }
+
+    /// Changes the priority of a pending download, e.g. as it scrolls into or out of view.
+    ///
+    /// Both downloads to disk and into memory of the given URL are affected. This has no effect if
+    /// the download has already started, or if it is not pending at all.
+    #[inline]
+    pub fn set_priority(&self, url: Url, priority: Priority) {
+        self.notify(Request::SetPriority(url, priority));
+    }
+
+    /// Overrides the [`Timeouts`] set in the [`FetcherConfig`] for future requests for `url`.
+    ///
+    /// This is useful for files which block the user interface while they are downloading, and
+    /// should therefore fail fast. Requests which have already been sent are not affected.
+    #[inline]
+    pub fn set_timeouts(&self, url: Url, timeouts: Timeouts) {
+        self.notify(Request::SetTimeouts(url, timeouts));
+    }
+
+    /// Cancels a pending download, discarding any partially downloaded data.
+    ///
+    /// No [`Completion`] event is sent for a cancelled download. If the file is requested again
+    /// later, it is downloaded from scratch. Cached files and failed downloads are not affected.
+    #[inline]
+    pub fn cancel(&self, url: Url) {
+        self.notify(Request::Cancel(url));
+    }
+
+    /// Forgets that the download of `url` failed, so that the next request for it starts over.
+    ///
+    /// Failed downloads are otherwise remembered for the lifetime of the `Fetcher`. This has no
+    /// effect on cached files and pending downloads.
+    #[inline]
+    pub fn forget_failure(&self, url: Url) {
+        self.notify(Request::ForgetFailure(url));
+    }
+
+    /// Returns an iterator over all completion events received so far, without blocking.
+    ///
+    /// Events are only sent for URLs passed to [`subscribe()`](Fetcher::subscribe()).
+    #[inline]
+    pub fn completions(&self) -> impl Iterator<Item = Completion> + '_ {
+        self.completion_rx.try_iter()
+    }
+
+    /// Returns the time at which the failed download of the given URL will be retried.
+    ///
+    /// While a download is cooling down in between attempts, [`poll_fetch()`](Fetcher::poll_fetch)
+    /// keeps returning `Response::Pending`, and the next attempt is started automatically once
+    /// this time has passed. Returns `None` if the download is not currently cooling down.
+    #[inline]
+    pub fn retry_at(&self, url: Url) -> Option<Instant> {
+        match self.request(Request::RetryAt(url)) {
+            Reply::RetryAt(retry_at) => retry_at,
+            reply => unreachable!("unexpected reply to retry request: {:?}", reply),
+        }
+    }
+
+    fn notify(&self, request: Request) {
         self.request_tx
-            .send(url)
+            .send(request)
+            .expect("failed to send request, receiver dropped");
+    }
+
+    fn request(&self, request: Request) -> Reply {
+        self.request_tx
+            .send(request)
             .expect("failed to send request, receiver dropped");
 
         self.response_rx
@@ -89,122 +451,736 @@ impl Drop for Fetcher {
 }
 
 /// Spawns the I/O background thread and returns a `Fetcher` handle for submitting new jobs.
-pub fn spawn() -> Fetcher {
+///
+/// Returns `Err` if a persistent cache directory was configured but could not be opened.
+pub fn spawn(config: FetcherConfig) -> anyhow::Result<Fetcher> {
+    let transport = match config.transport {
+        Some(ref transport) => transport.clone(),
+        None => HttpTransport::from_config(&config)
+            .map(Arc::new)
+            .context("could not initialize HTTP client")?,
+    };
+
+    let disk_cache = match config.persistent_cache {
+        Some(c) => DiskCache::open(&c.dir, c.max_size)
+            .map(Some)
+            .with_context(|| format!("could not open cache directory {}", c.dir.display()))?,
+        None => None,
+    };
+
     let (remote_task, abort_reg) = AbortHandle::new_pair();
     let (request_tx, request_rx) = flume::bounded(MAX_CHANNEL_CAP);
     let (response_tx, response_rx) = flume::bounded(MAX_CHANNEL_CAP);
-    let handle = std::thread::spawn(|| fetcher(request_rx, response_tx, abort_reg));
+    let (completion_tx, completion_rx) = flume::unbounded();
+
+    let worker = Worker {
+        cache: DownloadCache::new(Cache::new(disk_cache)),
+        policy: config.retry_policy,
+        transport,
+        timeouts: config.timeouts,
+        timeout_overrides: RefCell::default(),
+        scheduler: RefCell::new(Scheduler::new(config.max_concurrent_downloads)),
+        active: RefCell::default(),
+        memory: RefCell::new(MemoryCache::new(config.max_memory_size)),
+        subscribers: RefCell::default(),
+        json_subscribers: RefCell::default(),
+        completions: completion_tx,
+    };
+
+    let handle = std::thread::spawn(|| fetcher(request_rx, response_tx, abort_reg, worker));
 
-    Fetcher {
+    Ok(Fetcher {
         request_tx,
         response_rx,
+        completion_rx,
         remote_task,
         handle: Some(handle),
+    })
+}
+
+/// State shared by every download task running on the background thread.
+struct Worker {
+    cache: DownloadCache,
+    policy: RetryPolicy,
+    transport: Arc<dyn Transport>,
+    timeouts: Timeouts,
+    /// Timeouts which were set for individual URLs with [`Fetcher::set_timeouts()`].
+    timeout_overrides: RefCell<HashMap<Url, Timeouts>>,
+    scheduler: RefCell<Scheduler<Job>>,
+    /// Downloads which are currently in flight.
+    active: RefCell<HashMap<Job, ActiveDownload>>,
+    /// Downloads which are kept in memory rather than saved to disk.
+    memory: RefCell<MemoryCache>,
+    /// URLs of pending downloads which should send a [`Completion`] event once they finish.
+    subscribers: RefCell<HashSet<Url>>,
+    /// URLs of pending downloads into memory which should send a [`Completion`] event once they
+    /// finish, along with the decoders for their contents.
+    json_subscribers: RefCell<HashMap<Url, Decoder>>,
+    completions: Sender<Completion>,
+}
+
+impl Worker {
+    /// Sends a GET request for `url` through the transport, subject to the timeouts for `url`.
+    fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+        let overrides = self.timeout_overrides.borrow();
+        let timeouts = overrides.get(url).copied().unwrap_or(self.timeouts);
+        with_timeouts(self.transport.get(url, headers), timeouts)
     }
+
+    /// Returns the progress of a pending download. Queued downloads have not made any progress.
+    fn progress(&self, job: &Job) -> Progress {
+        self.active
+            .borrow()
+            .get(job)
+            .map(|download| download.progress)
+            .unwrap_or_default()
+    }
+
+    /// Records the progress of an active download.
+    fn set_progress(&self, job: &Job, progress: Progress) {
+        if let Some(download) = self.active.borrow_mut().get_mut(job) {
+            download.progress = progress;
+        }
+    }
+}
+
+/// Identifies a download in the [`Scheduler`], since the same URL may be downloaded both to disk
+/// and into memory at the same time.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Job {
+    /// A download which is saved to disk, e.g. requested with [`Fetcher::poll_fetch()`].
+    File(Url),
+    /// A download which is kept in memory, e.g. requested with [`Fetcher::poll_fetch_bytes()`].
+    Memory(Url),
+}
+
+/// A download which is currently in flight.
+struct ActiveDownload {
+    /// Handle for stopping the download task if it is cancelled.
+    abort: AbortHandle,
+    /// Progress of the download, which is updated by the download task.
+    progress: Progress,
 }
 
 /// Processes every incoming fetch request from `Fetcher` and emits a response. Jobs are executed
 /// concurrently on a single thread for maximum throughput.
 #[tokio::main(flavor = "current_thread")]
-async fn fetcher(incoming: Receiver<Request>, outgoing: Sender<Response>, reg: AbortRegistration) {
-    use tokio::task::{self, LocalSet};
+async fn fetcher(
+    incoming: Receiver<Request>,
+    outgoing: Sender<Reply>,
+    reg: AbortRegistration,
+    worker: Worker,
+) {
+    use tokio::task::LocalSet;
 
-    let client = Client::new();
-    let cache = Rc::new(DownloadCache::default());
+    let worker = Rc::new(worker);
     let pool = LocalSet::new();
 
+    let shared = worker.clone();
     let fetch_files = async move {
         let mut requests = incoming.into_stream();
 
-        while let Some(url) = requests.next().await {
-            task::spawn_local(process(
-                url,
-                client.clone(),
-                cache.clone(),
-                outgoing.clone(),
-            ));
+        while let Some(request) = requests.next().await {
+            let reply = match request {
+                Request::Fetch(url) => Reply::Fetch(process(url, Priority::default(), &shared)),
+                Request::Subscribe(url, priority) => {
+                    subscribe(url, priority, &shared);
+                    continue;
+                }
+                Request::SubscribeJson(url, decode, priority) => {
+                    memory::subscribe_json(url, decode, priority, &shared);
+                    continue;
+                }
+                Request::SetPriority(url, priority) => {
+                    let mut scheduler = shared.scheduler.borrow_mut();
+                    scheduler.set_priority(&Job::File(url.clone()), priority);
+                    scheduler.set_priority(&Job::Memory(url), priority);
+                    continue;
+                }
+                Request::SetTimeouts(url, timeouts) => {
+                    shared.timeout_overrides.borrow_mut().insert(url, timeouts);
+                    continue;
+                }
+                Request::Cancel(url) => {
+                    cancel(&url, &shared);
+                    continue;
+                }
+                Request::ForgetFailure(url) => {
+                    forget_failure(&url, &shared);
+                    continue;
+                }
+                Request::FetchBytes(url) => {
+                    let response = memory::process_bytes(url, Priority::default(), &shared);
+                    Reply::FetchBytes(response)
+                }
+                Request::FetchJson(url, decode) => {
+                    let response = memory::process_bytes(url, Priority::default(), &shared);
+                    Reply::FetchJson(response.and_then(decode))
+                }
+                Request::IsStale(url) => {
+                    let is_stale = shared.cache.borrow().is_stale(&url);
+                    Reply::IsStale(is_stale || memory::is_stale(&url, &shared))
+                }
+                Request::RetryAt(url) => {
+                    let retry_at = shared.cache.borrow().retry_at(&url);
+                    Reply::RetryAt(retry_at.or_else(|| memory::retry_at(&url, &shared)))
+                }
+            };
+
+            outgoing.send_async(reply).await.unwrap();
         }
     };
 
     let fetcher_task = Abortable::new(fetch_files, reg);
     pool.run_until(fetcher_task).await.ok();
+
+    // Save the most recent access times, so the least recently used files are evicted first.
+    worker.cache.borrow_mut().flush().ok();
+}
+
+/// Processes a requested URL, optionally queueing a new download and returning the current status.
+fn process(url: Url, priority: Priority, worker: &Rc<Worker>) -> Response {
+    // If this URL has been requested before, respond either "ready" or "pending".
+    let job = Job::File(url.clone());
+    let begun = begin_download(&url, &mut worker.cache.borrow_mut());
+    let (stale, attempts) = match begun {
+        Ok(begun) => begun,
+        Err(Response::Pending(_)) => return Response::Pending(worker.progress(&job)),
+        Err(response) => return response,
+    };
+
+    // This URL has either never been seen before or its cached copy has expired, so queue up the
+    // download in the background and quickly respond "pending" so the UI thread doesn't block.
+    let entry = Status::Pending {
+        stale,
+        attempts,
+        partial: None,
+    };
+    worker.cache.borrow_mut().entries.insert(url, entry);
+    worker.scheduler.borrow_mut().enqueue(job.clone(), priority);
+    start_downloads(worker);
+
+    Response::Pending(worker.progress(&job))
+}
+
+/// Starts downloading `url`, if necessary, and sends a completion event once it has finished.
+fn subscribe(url: Url, priority: Priority, worker: &Rc<Worker>) {
+    match process(url.clone(), priority, worker) {
+        Response::Ready(result) => {
+            let result = result.map(Payload::File);
+            worker.completions.send(Completion { url, result }).ok();
+        }
+        Response::Pending(_) => {
+            let job = Job::File(url.clone());
+            worker.scheduler.borrow_mut().set_priority(&job, priority);
+            worker.subscribers.borrow_mut().insert(url);
+        }
+    }
+}
+
+/// Stops a queued, active or cooling down download of `url`, whether it is saved to disk or kept
+/// in memory, and forgets its subscribers.
+fn cancel(url: &Url, worker: &Rc<Worker>) {
+    {
+        let job = Job::File(url.clone());
+        let mut scheduler = worker.scheduler.borrow_mut();
+        scheduler.remove(&job);
+
+        // Aborting the task drops its temporary file, which deletes the partial download.
+        if let Some(download) = worker.active.borrow_mut().remove(&job) {
+            download.abort.abort();
+            scheduler.deactivate(&job, true);
+        }
+    }
+
+    memory::cancel(url, worker);
+
+    worker.subscribers.borrow_mut().remove(url);
+
+    let mut cache = worker.cache.borrow_mut();
+    match cache.entries.remove(url) {
+        Some(Status::Pending { stale, .. }) | Some(Status::CoolingDown { stale, .. }) => {
+            // Keep serving the stale copy, which is revalidated again on the next request.
+            if let Some(stale) = stale {
+                cache.entries.insert(url.clone(), Status::Ready(Ok(stale)));
+            }
+        }
+        Some(entry) => {
+            cache.entries.insert(url.clone(), entry);
+        }
+        None => {}
+    }
+
+    drop(cache);
+    start_downloads(worker);
 }
 
-/// Processes a requested URL, optionally starting a new download and returning the current status.
-async fn process(url: Request, client: Client, cache: Rc<DownloadCache>, status: Sender<Response>) {
-    use std::collections::hash_map::Entry;
+/// Forgets a failed download of `url`, whether it was saved to disk or kept in memory.
+fn forget_failure(url: &Url, worker: &Worker) {
+    let mut cache = worker.cache.borrow_mut();
+    if let Some(Status::Ready(Err(_))) = cache.entries.get(url) {
+        cache.entries.remove(url);
+    }
+
+    memory::forget_failure(url, worker);
+}
 
-    let mut locked_cache = cache.borrow_mut();
-    match locked_cache.entry(url) {
-        Entry::Occupied(e) => {
-            // This URL has been requested before, so respond either "ready" or "pending".
-            // Remove entry if resulted in error, so download can be restarted on next request.
-            let response = match e.get() {
-                Poll::Ready(Ok(temp_path)) => Poll::Ready(Ok(temp_path.to_path_buf())),
-                Poll::Ready(Err(_)) => e.remove().map(|result| result.map(|_| unreachable!())),
-                Poll::Pending => Poll::Pending,
+/// Spawns the highest priority queued downloads until the concurrency limit is reached.
+fn start_downloads(worker: &Rc<Worker>) {
+    loop {
+        let job = match worker.scheduler.borrow_mut().next() {
+            Some(job) => job,
+            None => return,
+        };
+
+        let (abort, reg) = AbortHandle::new_pair();
+        let active = ActiveDownload {
+            abort,
+            progress: Progress::default(),
+        };
+        worker.active.borrow_mut().insert(job.clone(), active);
+
+        let worker = worker.clone();
+        match job {
+            Job::File(url) => spawn_abortable(download(url, worker), reg),
+            Job::Memory(url) => spawn_abortable(memory::download_bytes(url, worker), reg),
+        }
+    }
+}
+
+/// Spawns a download task on the current thread, which stops once `reg` is aborted.
+fn spawn_abortable<F>(task: F, reg: AbortRegistration)
+where
+    F: Future<Output = ()> + 'static,
+{
+    tokio::task::spawn_local(Abortable::new(task, reg));
+}
+
+/// Downloads the pending file at `url`, retrying according to the [`RetryPolicy`] until the
+/// download either succeeds or fails for good.
+///
+/// Retries are queued again once the download has cooled down, so that a failing download does
+/// not count towards the concurrency limit in between attempts. Interrupted downloads are resumed
+/// from where they left off, if the server supports range requests.
+async fn download(url: Url, worker: Rc<Worker>) {
+    // Stale files are revalidated with a conditional request.
+    let (headers, mut partial, temp_dir) = {
+        let mut cache = worker.cache.borrow_mut();
+        let temp_dir = cache.temp_dir();
+        match cache.entries.get_mut(&url) {
+            Some(Status::Pending { stale, partial, .. }) => {
+                let headers = stale
+                    .as_ref()
+                    .map_or_else(HeaderMap::new, |stale| validators(&stale.metadata));
+                (headers, partial.take(), temp_dir)
+            }
+            _ => (HeaderMap::new(), None, temp_dir),
+        }
+    };
+
+    let job = Job::File(url.clone());
+    let report = |progress| worker.set_progress(&job, progress);
+    let result = loop {
+        let mut headers = headers.clone();
+        if let Some(ref partial) = partial {
+            headers.extend(range_headers(partial));
+        }
+
+        let resuming = partial.is_some();
+        let request = worker.get(&url, headers);
+        match download_file(request, temp_dir.clone(), &mut partial, &report).await {
+            // The partial file could not be resumed, so start over from scratch right away.
+            Err(FetchError::Status(StatusCode::RANGE_NOT_SATISFIABLE)) if resuming => {}
+            result => break result,
+        }
+    };
+
+    worker.active.borrow_mut().remove(&job);
+    let delay = finish_download(&url, result, partial, &worker);
+    worker
+        .scheduler
+        .borrow_mut()
+        .deactivate(&job, delay.is_none());
+    start_downloads(&worker);
+
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+
+        if resume_download(&url, &worker) {
+            worker.scheduler.borrow_mut().requeue(job);
+            start_downloads(&worker);
+        }
+    }
+}
+
+/// Stores the outcome of a download attempt and notifies the subscribers if it is final.
+///
+/// Returns the delay until the next attempt if the download failed but will be retried. In that
+/// case, the partial file of an interrupted download is kept until the next attempt.
+fn finish_download(
+    url: &Url,
+    result: DownloadResult,
+    partial: Option<Partial>,
+    worker: &Worker,
+) -> Option<Duration> {
+    let mut cache = worker.cache.borrow_mut();
+    let (stale, attempts) = match cache.entries.remove(url) {
+        Some(Status::Pending {
+            stale, attempts, ..
+        }) => (stale, attempts + 1),
+        _ => (None, 1),
+    };
+
+    let mut result = match (result, stale) {
+        (Ok(Fetched::Modified(download)), _) => {
+            cache.store(url, download).map_err(FetchError::from)
+        }
+        (Ok(Fetched::NotModified(metadata)), Some(stale)) => {
+            Ok(cache.refresh(url, stale, metadata))
+        }
+        (Ok(Fetched::NotModified(_)), None) => Err(FetchError::Status(StatusCode::NOT_MODIFIED)),
+        (Err(error), stale) => match worker.policy.delay(&error, attempts) {
+            Some(delay) => {
+                // Keep the failed download around until it is time to try again, instead of
+                // restarting it immediately on the next request.
+                let retry_at = Instant::now() + delay;
+                let entry = Status::CoolingDown {
+                    stale,
+                    attempts,
+                    partial,
+                    retry_at,
+                };
+                cache.entries.insert(url.clone(), entry);
+                return Some(delay);
+            }
+            None => Err(error),
+        },
+    };
+
+    if worker.subscribers.borrow_mut().remove(url) {
+        let response = match result {
+            Ok(ref mut file) => {
+                file.validated = false;
+                Ok(Payload::File(file.path().to_path_buf()))
+            }
+            Err(ref e) => Err(e.clone()),
+        };
+
+        let url = url.clone();
+        worker
+            .completions
+            .send(Completion {
+                url,
+                result: response,
+            })
+            .ok();
+    }
+
+    cache.entries.insert(url.clone(), Status::Ready(result));
+    None
+}
+
+/// Marks a download which has finished cooling down as pending again.
+///
+/// Returns `false` if the download is no longer cooling down and should not be resumed.
+fn resume_download(url: &Url, worker: &Worker) -> bool {
+    let mut cache = worker.cache.borrow_mut();
+    match cache.entries.remove(url) {
+        Some(Status::CoolingDown {
+            stale,
+            attempts,
+            partial,
+            ..
+        }) => {
+            let entry = Status::Pending {
+                stale,
+                attempts,
+                partial,
             };
+            cache.entries.insert(url.clone(), entry);
+            true
+        }
+        Some(entry) => {
+            cache.entries.insert(url.clone(), entry);
+            false
+        }
+        None => false,
+    }
+}
 
-            status.send_async(response).await.unwrap()
+/// Checks whether a download of `url` needs to be started.
+///
+/// Returns `Err(_)` with the response to send back if the file is already fresh, still pending,
+/// cooling down in between attempts or failed to download for good. Otherwise, returns the stale
+/// cached copy to revalidate, if any, along with the number of failed attempts so far.
+fn begin_download(url: &Url, cache: &mut Cache) -> Result<(Option<CachedFile>, u32), Response> {
+    // Files persisted to disk during a previous run can be returned immediately.
+    if !cache.entries.contains_key(url) {
+        if let Some(file) = cache.lookup(url) {
+            cache.entries.insert(url.clone(), Status::Ready(Ok(file)));
         }
-        Entry::Vacant(e) => {
-            // This URL has never been seen before, so quickly respond "pending" so the UI thread
-            // doesn't block, and then spin up the download in the meantime.
-            let url = e.key().clone();
-            let request = client.get(url.as_str());
-            e.insert(Poll::Pending);
-            drop(locked_cache);
-            status.send_async(Poll::Pending).await.unwrap();
+    }
 
-            let result = download_file(request).await;
-            *cache.borrow_mut().get_mut(&url).unwrap() = Poll::Ready(result);
+    match cache.entries.remove(url) {
+        Some(Status::Ready(Ok(mut file))) if file.validated || !file.metadata.is_stale() => {
+            file.validated = false;
+            let response = Response::Ready(Ok(file.path().to_path_buf()));
+            cache.entries.insert(url.clone(), Status::Ready(Ok(file)));
+            Err(response)
+        }
+        Some(Status::Ready(Err(e))) => {
+            let response = Response::Ready(Err(e.clone()));
+            cache.entries.insert(url.clone(), Status::Ready(Err(e)));
+            Err(response)
         }
+        Some(entry @ Status::Pending { .. }) | Some(entry @ Status::CoolingDown { .. }) => {
+            cache.entries.insert(url.clone(), entry);
+            Err(Response::Pending(Progress::default()))
+        }
+        Some(Status::Ready(Ok(stale))) => Ok((Some(stale), 0)),
+        None => Ok((None, 0)),
+    }
+}
+
+/// Returns the headers which ask the server to send the rest of an interrupted download.
+///
+/// If the file has changed on the server since, `If-Range` makes it send the whole file instead.
+fn range_headers(partial: &Partial) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Ok(value) = format!("bytes={}-", partial.size).parse() {
+        headers.insert(RANGE, value);
+    }
+
+    if let Ok(value) = partial.validator.parse() {
+        headers.insert(IF_RANGE, value);
+    }
+
+    headers
+}
+
+/// Returns the validator to send as `If-Range` when resuming a download of the given response.
+///
+/// Returns `None` if the server does not accept range requests or did not send a suitable
+/// validator, in which case an interrupted download cannot be resumed safely. Weak `ETag`s are not
+/// allowed in `If-Range`, so `Last-Modified` is used instead if present.
+fn resume_validator(headers: &HeaderMap) -> Option<String> {
+    let get = |name: HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if get(ACCEPT_RANGES) == Some("none") {
+        return None;
+    }
+
+    match get(ETAG) {
+        Some(etag) if !etag.starts_with("W/") => Some(etag.to_owned()),
+        _ => get(LAST_MODIFIED).map(ToOwned::to_owned),
+    }
+}
+
+/// Returns the offset of the first byte in the body of a `206 Partial Content` response.
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Deserializes a JSON document into a `T`, erasing its type so it can be sent back to `Fetcher`.
+fn decode_json<T>(bytes: Bytes) -> Result<Box<dyn Any + Send + Sync>, FetchError>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    let value: T = serde_json::from_slice(&bytes)?;
+    Ok(Box::new(value))
+}
+
+/// Returns the headers which turn a request for a stale file into a conditional request.
+fn validators(metadata: &Metadata) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Some(value) = metadata.etag.as_ref().and_then(|v| v.parse().ok()) {
+        headers.insert(IF_NONE_MATCH, value);
+    }
+
+    if let Some(value) = metadata.last_modified.as_ref().and_then(|v| v.parse().ok()) {
+        headers.insert(IF_MODIFIED_SINCE, value);
     }
+
+    headers
 }
 
-/// Executes the GET request and saves the response data to disk in a temporary file.
-async fn download_file(request: RequestBuilder) -> anyhow::Result<TempPath> {
+/// The outcome of a single download attempt.
+type DownloadResult = Result<Fetched, FetchError>;
+
+/// The result of a successful GET request.
+#[derive(Debug)]
+enum Fetched {
+    /// The server sent back the contents of the file.
+    Modified(Download),
+    /// The server confirmed that the stale copy of the file is still up to date.
+    NotModified(Metadata),
+}
+
+/// Awaits the response to a GET request and saves its body to disk in a temporary file.
+///
+/// The temporary file is created in `temp_dir`, if specified, or in the OS temporary directory
+/// otherwise. If the request was conditional and the server responds with `304 Not Modified`, no
+/// file is created at all. `report` is called with the progress after every chunk of the body.
+///
+/// If `partial` contains an interrupted download and the server responds with `206 Partial
+/// Content`, the body is appended to it. If the body is interrupted in turn, the data received so
+/// far is stored in `partial` for the next attempt. Returns `416 Range Not Satisfiable` if the
+/// partial download cannot be resumed and should be discarded.
+async fn download_file<F, R>(
+    request: F,
+    temp_dir: Option<PathBuf>,
+    partial: &mut Option<Partial>,
+    mut report: R,
+) -> DownloadResult
+where
+    F: Future<Output = TransportResult>,
+    R: FnMut(Progress),
+{
+    use fnv::FnvHasher;
+    use std::hash::Hasher;
     use tokio::io::AsyncWriteExt;
 
-    let temp_file = tempfile::NamedTempFile::new()?;
-    let (std, temp_path) = temp_file.into_parts();
+    let response = request.await?;
+    let metadata = Metadata::from_headers(&response.headers);
+    match response.status {
+        StatusCode::NOT_MODIFIED => {
+            *partial = None;
+            return Ok(Fetched::NotModified(metadata));
+        }
+        status if !status.is_success() => {
+            // Keep the partial file around for the next attempt, unless the range was rejected.
+            if status == StatusCode::RANGE_NOT_SATISFIABLE {
+                *partial = None;
+            }
+
+            return Err(FetchError::Status(status));
+        }
+        _ => {}
+    }
+
+    // Servers which ignore the range, or whose copy of the file has changed in the meantime, send
+    // back the whole file with `200 OK` instead, so the partial file is discarded.
+    let resumed = match partial.take() {
+        Some(p) if response.status == StatusCode::PARTIAL_CONTENT => {
+            if content_range_start(&response.headers) != Some(p.size) {
+                return Err(FetchError::Status(StatusCode::RANGE_NOT_SATISFIABLE));
+            }
+
+            Some(p)
+        }
+        _ => None,
+    };
+
+    let (std, temp_path, mut hasher, mut size, metadata, validator) = match resumed {
+        Some(p) => {
+            let std = std::fs::OpenOptions::new().append(true).open(&p.path)?;
+            let metadata = metadata.merge(&p.metadata);
+            let hasher = FnvHasher::with_key(p.hash);
+            (std, p.path, hasher, p.size, metadata, Some(p.validator))
+        }
+        None => {
+            let temp_file = match temp_dir {
+                Some(dir) => tempfile::NamedTempFile::new_in(dir)?,
+                None => tempfile::NamedTempFile::new()?,
+            };
+
+            let (std, temp_path) = temp_file.into_parts();
+            let validator = resume_validator(&response.headers);
+            (std, temp_path, FnvHasher::default(), 0, metadata, validator)
+        }
+    };
+
     let mut file = tokio::fs::File::from_std(std);
+    let mut stream = response.body;
 
-    let response = request.send().await?;
-    let mut stream = response.bytes_stream();
+    let started = Instant::now();
+    let mut progress = Progress::from_headers(&response.headers);
+    progress.received = size;
+    progress.resumed = size;
+    progress.total = progress.total.map(|total| total + size);
+    report(progress);
 
     while let Some(result) = stream.next().await {
-        let bytes = result.context("could not decode HTTP response body")?;
+        let bytes = match result {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                // Keep the data received so far, so the next attempt can pick up where we left off.
+                if let (Some(validator), Ok(())) = (validator, file.flush().await) {
+                    *partial = Some(Partial {
+                        path: temp_path,
+                        size,
+                        hash: hasher.finish(),
+                        validator,
+                        metadata,
+                    });
+                }
+
+                return Err(error);
+            }
+        };
+
         file.write_all(&bytes[..]).await?;
+        hasher.write(&bytes[..]);
+        size += bytes.len() as u64;
+
+        progress.received = size;
+        progress.elapsed = started.elapsed();
+        report(progress);
     }
 
-    Ok(temp_path)
+    file.flush().await?;
+
+    Ok(Fetched::Modified(Download {
+        path: temp_path,
+        hash: hasher.finish(),
+        size,
+        metadata,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
-    use futures_util::future::{self, FutureExt};
+    use std::io;
+    use std::sync::Mutex;
+
+    use futures_util::future::{self, FutureExt, LocalBoxFuture};
+    use reqwest::header::{HeaderValue, CACHE_CONTROL, CONTENT_LENGTH};
 
     use super::*;
 
     const EXAMPLE_URL: &str = "http://example.com";
+    const EXAMPLE_HTML: &str = "<html></html>";
+
+    fn spawn_example() -> (Fetcher, MemoryTransport) {
+        let transport = MemoryTransport::new();
+        transport.insert(EXAMPLE_URL.parse().unwrap(), EXAMPLE_HTML);
+
+        let config = FetcherConfig::new().with_transport(transport.clone());
+        (spawn(config).unwrap(), transport)
+    }
 
     #[test]
     fn downloads_file_blocking() {
-        let fetcher = spawn();
-        let _html_path = fetcher
+        let (fetcher, _) = spawn_example();
+        let html_path = fetcher
             .fetch(EXAMPLE_URL.parse().unwrap())
             .expect("failed to download page");
+
+        assert_eq!(std::fs::read_to_string(html_path).unwrap(), EXAMPLE_HTML);
     }
 
     #[tokio::test]
     async fn downloads_file_concurrently() {
-        let fetcher = spawn();
+        let (fetcher, transport) = spawn_example();
 
         let url: Url = EXAMPLE_URL.parse().unwrap();
         let jobs = (0..10).map(|_| {
             future::poll_fn(|cx| {
-                let poll = fetcher.poll_fetch(url.clone());
+                let poll = fetcher.poll_fetch(url.clone()).into_poll();
                 if poll.is_pending() {
                     cx.waker().wake_by_ref();
                 }
@@ -216,5 +1192,416 @@ mod tests {
         future::try_join_all(jobs)
             .await
             .expect("one of the downloads failed");
+
+        assert_eq!(transport.requests(), 1);
+    }
+
+    #[test]
+    fn notifies_subscribers() {
+        let (fetcher, transport) = spawn_example();
+        let url: Url = EXAMPLE_URL.parse().unwrap();
+        fetcher.subscribe(url.clone(), Priority::default());
+        fetcher.subscribe(url.clone(), Priority::default());
+
+        let completion = fetcher
+            .completion_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no completion event received");
+
+        assert_eq!(completion.url, url);
+        let payload = completion.result.expect("failed to download page");
+        let path = payload.path().expect("file was not saved to disk");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), EXAMPLE_HTML);
+        assert_eq!(transport.requests(), 1);
+    }
+
+    /// Serves files from a [`MemoryTransport`], except for `/stalled` which never responds and
+    /// `/partial` which stops responding after sending the first 5 out of 10 bytes.
+    #[derive(Debug)]
+    struct StalledTransport(MemoryTransport);
+
+    impl Transport for StalledTransport {
+        fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+            use futures_util::stream;
+
+            match url.path() {
+                "/stalled" => future::pending().boxed_local(),
+                "/partial" => {
+                    let mut response = TransportResponse::from_bytes(HeaderMap::new(), "hello");
+                    response
+                        .headers
+                        .insert(CONTENT_LENGTH, HeaderValue::from(10));
+                    response.body = response.body.chain(stream::pending()).boxed_local();
+                    future::ok(response).boxed_local()
+                }
+                _ => self.0.get(url, headers),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_download_progress() {
+        let config = FetcherConfig::new().with_transport(StalledTransport(MemoryTransport::new()));
+        let fetcher = spawn(config).unwrap();
+        let url: Url = "http://example.com/partial".parse().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let progress = loop {
+            match fetcher.poll_fetch(url.clone()) {
+                Response::Pending(progress) if progress.received > 0 => break progress,
+                Response::Pending(_) if Instant::now() < deadline => {}
+                response => panic!("download did not make progress: {:?}", response),
+            }
+        };
+
+        assert_eq!(progress.received, 5);
+        assert_eq!(progress.total, Some(10));
+        assert_eq!(progress.fraction(), Some(0.5));
+    }
+
+    #[test]
+    fn overrides_timeouts_per_url() {
+        let config = FetcherConfig::new()
+            .with_transport(StalledTransport(MemoryTransport::new()))
+            .with_retry_policy(RetryPolicy::never())
+            .with_read_timeout(Duration::from_secs(3600));
+        let fetcher = spawn(config).unwrap();
+
+        // The stalled request would only time out after an hour, unless the override applies.
+        let url: Url = "http://example.com/stalled".parse().unwrap();
+        let timeouts = Timeouts {
+            read: Some(Duration::from_millis(20)),
+            total: None,
+        };
+        fetcher.set_timeouts(url.clone(), timeouts);
+
+        let started = Instant::now();
+        let error = fetcher.fetch(url).expect_err("stalled download finished");
+        assert!(matches!(error, FetchError::Timeout));
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn schedules_downloads_into_memory() {
+        let transport = MemoryTransport::new();
+        transport.insert(EXAMPLE_URL.parse().unwrap(), EXAMPLE_HTML);
+
+        let config = FetcherConfig::new()
+            .with_transport(StalledTransport(transport.clone()))
+            .with_max_concurrent_downloads(1);
+        let fetcher = spawn(config).unwrap();
+
+        let partial: Url = "http://example.com/partial".parse().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match fetcher.poll_fetch_bytes(partial.clone()) {
+                Response::Pending(progress) if progress.received == 5 => break,
+                Response::Pending(_) if Instant::now() < deadline => {}
+                response => panic!("download did not make progress: {:?}", response),
+            }
+        }
+
+        // The stalled download occupies the only slot, so the next one is queued behind it.
+        let url: Url = EXAMPLE_URL.parse().unwrap();
+        assert!(fetcher.poll_fetch_bytes(url.clone()).is_pending());
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(fetcher.poll_fetch_bytes(url.clone()).is_pending());
+        assert_eq!(transport.requests(), 0);
+
+        fetcher.cancel(partial);
+        assert_eq!(
+            &fetcher.fetch_bytes(url).unwrap()[..],
+            EXAMPLE_HTML.as_bytes()
+        );
+        assert_eq!(transport.requests(), 1);
+    }
+
+    #[test]
+    fn reprioritizes_downloads_into_memory() {
+        let transport = MemoryTransport::new();
+        transport.insert(EXAMPLE_URL.parse().unwrap(), EXAMPLE_HTML);
+
+        let config = FetcherConfig::new()
+            .with_transport(StalledTransport(transport.clone()))
+            .with_max_concurrent_downloads(1);
+        let fetcher = spawn(config).unwrap();
+
+        // The stalled download occupies the only slot, so the others have to wait in the queue.
+        let stalled: Url = "http://example.com/stalled?first".parse().unwrap();
+        let queued: Url = "http://example.com/stalled?second".parse().unwrap();
+        let url: Url = EXAMPLE_URL.parse().unwrap();
+        fetcher.subscribe(stalled.clone(), Priority::default());
+        fetcher.subscribe(queued, Priority(1));
+        assert!(fetcher.poll_fetch_bytes(url.clone()).is_pending());
+        fetcher.set_priority(url.clone(), Priority(2));
+
+        // Had the download into memory kept its default priority, it would be stuck behind the
+        // second stalled download.
+        fetcher.cancel(stalled);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match fetcher.poll_fetch_bytes(url.clone()) {
+                Response::Ready(result) => {
+                    assert_eq!(&result.unwrap()[..], EXAMPLE_HTML.as_bytes());
+                    break;
+                }
+                Response::Pending(_) if Instant::now() < deadline => {}
+                Response::Pending(_) => panic!("download into memory was not reprioritized"),
+            }
+        }
+    }
+
+    /// Serves `helloworld`, but drops the connection after `hello` on the first request. Range
+    /// requests are only honored if `honor_range` is set, and stall after `wor` if
+    /// `stall_resumed` is set.
+    #[derive(Debug, Default)]
+    struct InterruptedTransport {
+        honor_range: bool,
+        stall_resumed: bool,
+        requests: Arc<Mutex<Vec<HeaderMap>>>,
+    }
+
+    impl Transport for InterruptedTransport {
+        fn get(&self, _: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+            use futures_util::stream;
+
+            let mut requests = self.requests.lock().unwrap();
+            let mut response_headers = HeaderMap::new();
+            response_headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+            let response = if requests.is_empty() {
+                let mut response = TransportResponse::from_bytes(response_headers, "hello");
+                let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+                let error = stream::once(future::err(reset.into()));
+                response.body = response.body.chain(error).boxed_local();
+                response
+            } else if self.honor_range && headers.contains_key(RANGE) {
+                let range = HeaderValue::from_static("bytes 5-9/10");
+                response_headers.insert(CONTENT_RANGE, range);
+                let mut response = if self.stall_resumed {
+                    let mut response = TransportResponse::from_bytes(response_headers, "wor");
+                    response
+                        .headers
+                        .insert(CONTENT_LENGTH, HeaderValue::from(5));
+                    response.body = response.body.chain(stream::pending()).boxed_local();
+                    response
+                } else {
+                    TransportResponse::from_bytes(response_headers, "world")
+                };
+                response.status = StatusCode::PARTIAL_CONTENT;
+                response
+            } else {
+                TransportResponse::from_bytes(response_headers, "helloworld")
+            };
+
+            requests.push(headers);
+            future::ok(response).boxed_local()
+        }
+    }
+
+    #[test]
+    fn resumes_interrupted_downloads() {
+        for &honor_range in &[true, false] {
+            let requests = Arc::new(Mutex::default());
+            let transport = InterruptedTransport {
+                honor_range,
+                requests: requests.clone(),
+                ..Default::default()
+            };
+
+            let policy = RetryPolicy::new().with_base_delay(Duration::from_millis(1));
+            let config = FetcherConfig::new()
+                .with_transport(transport)
+                .with_retry_policy(policy);
+            let fetcher = spawn(config).unwrap();
+
+            let path = fetcher.fetch(EXAMPLE_URL.parse().unwrap()).unwrap();
+            assert_eq!(std::fs::read_to_string(path).unwrap(), "helloworld");
+
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[1][RANGE], "bytes=5-");
+            assert_eq!(requests[1][IF_RANGE], "\"v1\"");
+        }
+    }
+
+    #[test]
+    fn reports_throughput_of_resumed_downloads() {
+        let transport = InterruptedTransport {
+            honor_range: true,
+            stall_resumed: true,
+            ..Default::default()
+        };
+
+        let policy = RetryPolicy::new().with_base_delay(Duration::from_millis(1));
+        let config = FetcherConfig::new()
+            .with_transport(transport)
+            .with_retry_policy(policy);
+        let fetcher = spawn(config).unwrap();
+        let url: Url = EXAMPLE_URL.parse().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let progress = loop {
+            match fetcher.poll_fetch(url.clone()) {
+                Response::Pending(progress) if progress.received == 8 => break progress,
+                Response::Pending(_) if Instant::now() < deadline => {}
+                response => panic!("download was not resumed: {:?}", response),
+            }
+        };
+
+        assert_eq!(progress.resumed, 5);
+        assert_eq!(progress.total, Some(10));
+        assert_eq!(progress.fraction(), Some(0.8));
+
+        // Only the 3 bytes received by the resumed attempt count towards the throughput.
+        let secs = progress.elapsed.as_secs_f64();
+        assert!(secs > 0.0);
+        assert!((progress.throughput() * secs - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn prioritizes_and_cancels_downloads() {
+        let base: Url = EXAMPLE_URL.parse().unwrap();
+        let (stalled, low, high) = (
+            base.join("stalled").unwrap(),
+            base.join("low").unwrap(),
+            base.join("high").unwrap(),
+        );
+
+        let files = MemoryTransport::new();
+        files.insert(low.clone(), EXAMPLE_HTML);
+        files.insert(high.clone(), EXAMPLE_HTML);
+
+        let config = FetcherConfig::new()
+            .with_transport(StalledTransport(files))
+            .with_max_concurrent_downloads(1);
+        let fetcher = spawn(config).unwrap();
+
+        // The stalled download occupies the only slot, so the others have to wait in the queue.
+        fetcher.subscribe(stalled.clone(), Priority::default());
+        fetcher.subscribe(low.clone(), Priority(-1));
+        fetcher.subscribe(high.clone(), Priority(-2));
+        fetcher.set_priority(high.clone(), Priority(1));
+
+        let timeout = Duration::from_millis(100);
+        assert!(fetcher.completion_rx.recv_timeout(timeout).is_err());
+
+        fetcher.cancel(stalled);
+        let timeout = Duration::from_secs(5);
+        let first = fetcher.completion_rx.recv_timeout(timeout).unwrap();
+        let second = fetcher.completion_rx.recv_timeout(timeout).unwrap();
+        assert_eq!((first.url, second.url), (high, low));
+        assert!(first.result.is_ok() && second.result.is_ok());
+    }
+
+    #[test]
+    fn fetches_bytes_and_json() {
+        let transport = MemoryTransport::new();
+        let url: Url = "http://example.com/data.json".parse().unwrap();
+        transport.insert(url.clone(), r#"{"title": "Home"}"#);
+        transport.insert(EXAMPLE_URL.parse().unwrap(), EXAMPLE_HTML);
+
+        let config = FetcherConfig::new().with_transport(transport.clone());
+        let fetcher = spawn(config).unwrap();
+
+        let bytes = fetcher.fetch_bytes(url.clone()).unwrap();
+        assert_eq!(&bytes[..], br#"{"title": "Home"}"#);
+
+        let json: HashMap<String, String> = fetcher.fetch_json(url).unwrap();
+        assert_eq!(json["title"], "Home");
+        assert_eq!(transport.requests(), 1);
+
+        let error = fetcher
+            .fetch_json::<HashMap<String, String>>(EXAMPLE_URL.parse().unwrap())
+            .expect_err("HTML was decoded as JSON");
+        assert!(matches!(error, FetchError::Decode(_)));
+    }
+
+    #[test]
+    fn notifies_json_subscribers() {
+        let transport = MemoryTransport::new();
+        let url: Url = "http://example.com/data.json".parse().unwrap();
+        transport.insert(url.clone(), r#"{"title": "Home"}"#);
+
+        let config = FetcherConfig::new().with_transport(transport.clone());
+        let fetcher = spawn(config).unwrap();
+        fetcher.subscribe_json::<HashMap<String, String>>(url.clone(), Priority::default());
+
+        let completion = fetcher
+            .completion_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no completion event received");
+
+        assert_eq!(completion.url, url);
+        let payload = completion.result.expect("failed to download document");
+        assert!(payload.path().is_none());
+        let json = payload
+            .json::<HashMap<String, String>>()
+            .expect("document was not decoded");
+        assert_eq!(json["title"], "Home");
+
+        // Finished documents are decoded and sent right away.
+        fetcher.subscribe_json::<HashMap<String, String>>(url, Priority::default());
+        let completion = fetcher
+            .completion_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no completion event received");
+        assert!(completion
+            .result
+            .unwrap()
+            .json::<HashMap<String, String>>()
+            .is_some());
+        assert_eq!(transport.requests(), 1);
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let (fetcher, transport) = spawn_example();
+        let error = fetcher
+            .fetch("http://example.com/missing".parse().unwrap())
+            .expect_err("missing file was downloaded");
+
+        assert!(error.is_not_found());
+        assert_eq!(transport.requests(), 1);
+    }
+
+    #[test]
+    fn forgets_failed_downloads() {
+        let (fetcher, transport) = spawn_example();
+        let url: Url = "http://example.com/late".parse().unwrap();
+        assert!(fetcher.fetch(url.clone()).is_err());
+        assert!(fetcher.fetch_bytes(url.clone()).is_err());
+
+        transport.insert(url.clone(), EXAMPLE_HTML);
+        assert!(fetcher.fetch(url.clone()).is_err());
+
+        fetcher.forget_failure(url.clone());
+        let path = fetcher
+            .fetch(url.clone())
+            .expect("failure was not forgotten");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), EXAMPLE_HTML);
+        assert_eq!(
+            &fetcher.fetch_bytes(url).unwrap()[..],
+            EXAMPLE_HTML.as_bytes()
+        );
+        assert_eq!(transport.requests(), 4);
+    }
+
+    #[test]
+    fn revalidates_stale_files() {
+        let (fetcher, transport) = spawn_example();
+        let url: Url = EXAMPLE_URL.parse().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        transport.insert_response(url.clone(), StatusCode::OK, headers, EXAMPLE_HTML);
+
+        let first = fetcher.fetch(url.clone()).unwrap();
+        assert!(fetcher.is_stale(url.clone()));
+
+        let second = fetcher.fetch(url).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(transport.requests(), 2);
     }
 }
@@ -0,0 +1,754 @@
+//! In-memory and on-disk caching of downloaded files.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use reqwest::header::{HeaderMap, CACHE_CONTROL, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+use url::Url;
+
+use super::FetchError;
+
+const INDEX_FILE_NAME: &str = "index.json";
+const INDEX_TEMP_FILE_NAME: &str = "index.json.tmp";
+const OBJECTS_DIR_NAME: &str = "objects";
+const TEMP_DIR_NAME: &str = "tmp";
+
+/// How long responses without an explicit `Cache-Control: max-age` stay fresh, in seconds.
+const HEURISTIC_MAX_AGE: u64 = 10 * 60;
+
+/// A finished download staged in a temporary file, along with a hash of its contents.
+#[derive(Debug)]
+pub(super) struct Download {
+    /// Temporary file containing the downloaded data.
+    pub path: TempPath,
+    /// FNV-1a hash of the file contents.
+    pub hash: u64,
+    /// Size of the file, in bytes.
+    pub size: u64,
+    /// Caching metadata extracted from the response headers.
+    pub metadata: Metadata,
+}
+
+/// A download which was interrupted part of the way through, kept so that it can be resumed.
+#[derive(Debug)]
+pub(super) struct Partial {
+    /// Temporary file containing the data received so far.
+    pub path: TempPath,
+    /// Number of bytes received so far.
+    pub size: u64,
+    /// FNV-1a hash state of the data received so far.
+    pub hash: u64,
+    /// Validator identifying the version of the file being downloaded, sent as `If-Range`.
+    pub validator: String,
+    /// Caching metadata extracted from the response headers.
+    pub metadata: Metadata,
+}
+
+/// HTTP caching metadata for a downloaded file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(super) struct Metadata {
+    /// Value of the `ETag` response header, if any.
+    pub etag: Option<String>,
+    /// Value of the `Last-Modified` response header, if any.
+    pub last_modified: Option<String>,
+    /// Number of seconds the file stays fresh for, according to the `Cache-Control` header.
+    pub max_age: Option<u64>,
+    /// Time at which the file was downloaded or last revalidated, in seconds since the UNIX epoch.
+    pub fetched_at: u64,
+}
+
+impl Metadata {
+    /// Extracts the validators and freshness lifetime from a set of HTTP response headers.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+
+        let max_age = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|directive| match directive.trim() {
+                "no-cache" | "no-store" => Some(0),
+                d if d.starts_with("max-age=") => d["max-age=".len()..].parse().ok(),
+                _ => None,
+            })
+            .min();
+
+        Metadata {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+            max_age,
+            fetched_at: unix_now(),
+        }
+    }
+
+    /// Returns `true` if the file must be revalidated with the server before it can be reused.
+    pub fn is_stale(&self) -> bool {
+        let max_age = self.max_age.unwrap_or(HEURISTIC_MAX_AGE);
+        unix_now().saturating_sub(self.fetched_at) >= max_age
+    }
+
+    /// Combines metadata from a `304 Not Modified` response with that of the stale file.
+    ///
+    /// Servers may omit validators from `304` responses, in which case the old ones still apply.
+    pub fn merge(self, stale: &Metadata) -> Self {
+        Metadata {
+            etag: self.etag.or_else(|| stale.etag.clone()),
+            last_modified: self.last_modified.or_else(|| stale.last_modified.clone()),
+            max_age: self.max_age.or(stale.max_age),
+            fetched_at: self.fetched_at,
+        }
+    }
+}
+
+/// A downloaded file stored in the cache.
+#[derive(Debug)]
+pub(super) struct CachedFile {
+    storage: Storage,
+    /// HTTP caching metadata for the file.
+    pub metadata: Metadata,
+    /// Whether the file was just downloaded or revalidated and has not been handed out yet.
+    ///
+    /// Such files are returned even if they are already stale again, e.g. due to `no-cache`, so
+    /// that every request is guaranteed to complete.
+    pub validated: bool,
+}
+
+impl CachedFile {
+    /// Returns the location of the file on disk.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        match self.storage {
+            Storage::Temporary(ref path) => path,
+            Storage::Persistent(ref path) => path,
+        }
+    }
+}
+
+/// A list of locations where a [`CachedFile`] can be stored.
+#[derive(Debug)]
+enum Storage {
+    /// A file in the OS temporary directory which is deleted once dropped.
+    Temporary(TempPath),
+    /// A file in the persistent cache directory, managed by a [`DiskCache`].
+    Persistent(PathBuf),
+}
+
+/// The download status of a URL.
+#[derive(Debug)]
+pub(super) enum Status {
+    /// The file is currently being downloaded.
+    ///
+    /// If a stale copy of the file exists, it is kept here while it is being revalidated. If a
+    /// previous attempt was interrupted, the data received so far is kept here until it resumes.
+    Pending {
+        stale: Option<CachedFile>,
+        attempts: u32,
+        partial: Option<Partial>,
+    },
+    /// The download failed and will be retried once `retry_at` has passed.
+    CoolingDown {
+        stale: Option<CachedFile>,
+        attempts: u32,
+        partial: Option<Partial>,
+        retry_at: Instant,
+    },
+    /// The download has either finished or failed for good.
+    Ready(Result<CachedFile, FetchError>),
+}
+
+/// An in-memory cache of pending and completed downloads, keyed by their URLs.
+///
+/// Completed downloads are kept in the OS temporary directory unless a [`DiskCache`] is provided,
+/// in which case they are persisted there instead.
+#[derive(Debug)]
+pub(super) struct Cache {
+    /// Download status of every URL requested so far during this run.
+    pub entries: HashMap<Url, Status>,
+    disk: Option<DiskCache>,
+}
+
+impl Cache {
+    /// Creates a new `Cache`, optionally backed by the given persistent [`DiskCache`].
+    pub fn new(disk: Option<DiskCache>) -> Self {
+        Cache {
+            entries: HashMap::default(),
+            disk,
+        }
+    }
+
+    /// Returns the directory where in-progress downloads should be staged.
+    ///
+    /// Returns `None` if the OS temporary directory should be used.
+    pub fn temp_dir(&self) -> Option<PathBuf> {
+        self.disk.as_ref().map(DiskCache::temp_dir)
+    }
+
+    /// Looks up a file which was persisted to disk during a previous run of the application.
+    pub fn lookup(&mut self, url: &Url) -> Option<CachedFile> {
+        let disk = self.disk.as_mut()?;
+        disk.get(url).map(|(path, metadata)| CachedFile {
+            storage: Storage::Persistent(path),
+            metadata,
+            validated: false,
+        })
+    }
+
+    /// Returns `true` if the cached copy of `url` has expired and must be revalidated.
+    ///
+    /// Returns `false` if the file is still fresh, or if it has not been downloaded at all.
+    pub fn is_stale(&self, url: &Url) -> bool {
+        match self.entries.get(url) {
+            Some(Status::Ready(Ok(file))) => file.metadata.is_stale(),
+            Some(_) => false,
+            None => matches!(
+                self.disk.as_ref().and_then(|disk| disk.metadata(url)),
+                Some(metadata) if metadata.is_stale()
+            ),
+        }
+    }
+
+    /// Returns the time at which the failed download of `url` will be retried, if any.
+    pub fn retry_at(&self, url: &Url) -> Option<Instant> {
+        match self.entries.get(url) {
+            Some(Status::CoolingDown { retry_at, .. }) => Some(*retry_at),
+            _ => None,
+        }
+    }
+
+    /// Moves a finished download into the cache and returns its final location.
+    ///
+    /// If the cache is persistent, this may evict the least recently used files from disk.
+    pub fn store(&mut self, url: &Url, download: Download) -> io::Result<CachedFile> {
+        let metadata = download.metadata.clone();
+        let disk = match self.disk.as_mut() {
+            Some(disk) => disk,
+            None => {
+                return Ok(CachedFile {
+                    storage: Storage::Temporary(download.path),
+                    metadata,
+                    validated: true,
+                })
+            }
+        };
+
+        let (path, evicted) = disk.insert(url, download)?;
+
+        // Make sure evicted files are downloaded again the next time they are requested.
+        for url in evicted {
+            if let Some(Status::Ready(Ok(_))) = self.entries.get(&url) {
+                self.entries.remove(&url);
+            }
+        }
+
+        Ok(CachedFile {
+            storage: Storage::Persistent(path),
+            metadata,
+            validated: true,
+        })
+    }
+
+    /// Saves a file which was downloaded into memory to the persistent cache, if there is one.
+    pub fn persist(&mut self, url: &Url, bytes: &[u8], metadata: Metadata) -> io::Result<()> {
+        use fnv::FnvHasher;
+        use std::hash::Hasher;
+        use std::io::Write;
+
+        let dir = match self.temp_dir() {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(bytes)?;
+
+        let mut hasher = FnvHasher::default();
+        hasher.write(bytes);
+
+        let download = Download {
+            path: file.into_temp_path(),
+            hash: hasher.finish(),
+            size: bytes.len() as u64,
+            metadata,
+        };
+
+        // The previous copy may be deleted from disk, so make sure it is not handed out anymore.
+        if let Some(Status::Ready(Ok(_))) = self.entries.get(url) {
+            self.entries.remove(url);
+        }
+
+        self.store(url, download).map(drop)
+    }
+
+    /// Marks a stale file as fresh again after the server responded with `304 Not Modified`.
+    pub fn refresh(&mut self, url: &Url, mut stale: CachedFile, metadata: Metadata) -> CachedFile {
+        stale.metadata = metadata.merge(&stale.metadata);
+        stale.validated = true;
+        self.refresh_metadata(url, stale.metadata.clone());
+        stale
+    }
+
+    /// Replaces the metadata of the file persisted for `url` after it was revalidated, without
+    /// writing its contents again.
+    pub fn refresh_metadata(&mut self, url: &Url, metadata: Metadata) {
+        if let Some(disk) = self.disk.as_mut() {
+            disk.set_metadata(url, metadata);
+        }
+    }
+
+    /// Writes any pending changes to the persistent cache index to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.disk.as_mut() {
+            Some(disk) => disk.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A persistent cache of downloaded files which survives restarts.
+///
+/// Files are stored in a content-addressed layout, named after a hash of their contents, so
+/// identical files served from different URLs are only stored once. Since the hash is not
+/// collision-resistant, files are only deduplicated if their contents actually match. An index
+/// file maps every URL to its file. Once the combined size of the stored files exceeds the maximum size, the least
+/// recently used entries are evicted.
+#[derive(Debug)]
+pub(super) struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    index: Index,
+    dirty: bool,
+}
+
+impl DiskCache {
+    /// Opens the cache located in `dir`, creating it if it does not exist yet.
+    ///
+    /// If the index file is corrupt, the cache is cleared and started over from scratch.
+    pub fn open<P: Into<PathBuf>>(dir: P, max_size: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        let objects_dir = dir.join(OBJECTS_DIR_NAME);
+        fs::create_dir_all(&objects_dir)?;
+        fs::create_dir_all(dir.join(TEMP_DIR_NAME))?;
+
+        let mut index = match fs::read(dir.join(INDEX_FILE_NAME)) {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(index) => index,
+                Err(_) => {
+                    fs::remove_dir_all(&objects_dir)?;
+                    fs::create_dir_all(&objects_dir)?;
+                    Index::default()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => Index::default(),
+            Err(e) => return Err(e),
+        };
+
+        // Forget about any files which were deleted behind our back.
+        index
+            .entries
+            .retain(|_, entry| dir.join(&entry.path).is_file());
+
+        Ok(DiskCache {
+            dir,
+            max_size,
+            index,
+            dirty: false,
+        })
+    }
+
+    /// Returns the directory where in-progress downloads should be staged.
+    ///
+    /// This is located inside the cache directory so that finished downloads can be moved into
+    /// place with a cheap rename.
+    pub fn temp_dir(&self) -> PathBuf {
+        self.dir.join(TEMP_DIR_NAME)
+    }
+
+    /// Returns the location and metadata of the file downloaded from `url`, if it exists, and
+    /// marks it as the most recently used entry.
+    pub fn get(&mut self, url: &Url) -> Option<(PathBuf, Metadata)> {
+        let entry = self.index.entries.get_mut(url)?;
+        self.index.clock += 1;
+        entry.last_access = self.index.clock;
+        self.dirty = true;
+        Some((self.dir.join(&entry.path), entry.metadata.clone()))
+    }
+
+    /// Returns the metadata of the file downloaded from `url`, if it exists.
+    ///
+    /// Unlike [`DiskCache::get()`], this does not count as an access for eviction purposes.
+    pub fn metadata(&self, url: &Url) -> Option<&Metadata> {
+        self.index.entries.get(url).map(|entry| &entry.metadata)
+    }
+
+    /// Replaces the metadata of the file downloaded from `url`, if it exists.
+    pub fn set_metadata(&mut self, url: &Url, metadata: Metadata) {
+        if let Some(entry) = self.index.entries.get_mut(url) {
+            entry.metadata = metadata;
+            self.dirty = true;
+        }
+    }
+
+    /// Moves a finished download into the cache and saves the updated index to disk.
+    ///
+    /// Returns the final location of the file along with the URLs of any evicted entries.
+    pub fn insert(&mut self, url: &Url, download: Download) -> io::Result<(PathBuf, Vec<Url>)> {
+        let Download {
+            path,
+            hash,
+            size,
+            metadata,
+        } = download;
+        // Different contents may hash to the same value, so keep looking for either an identical
+        // copy or a free location for this one.
+        let mut collisions = 0;
+        let (rel_path, abs_path) = loop {
+            let rel_path = object_path(hash, collisions);
+            let abs_path = self.dir.join(&rel_path);
+
+            if !abs_path.is_file() {
+                fs::create_dir_all(abs_path.parent().expect("object paths have a parent"))?;
+                path.persist(&abs_path).map_err(|e| e.error)?;
+                break (rel_path, abs_path);
+            } else if has_contents(&abs_path, &path, size)? {
+                // Identical contents are already stored on disk, so we can discard this copy.
+                path.close()?;
+                break (rel_path, abs_path);
+            }
+
+            collisions += 1;
+        };
+
+        self.index.clock += 1;
+        let entry = IndexEntry {
+            path: rel_path,
+            size,
+            last_access: self.index.clock,
+            metadata,
+        };
+
+        // The contents served from this URL may have changed since it was last downloaded.
+        if let Some(old) = self.index.entries.insert(url.clone(), entry) {
+            self.remove_if_unreferenced(&old.path)?;
+        }
+
+        let evicted = self.evict(url)?;
+        self.save()?;
+
+        Ok((abs_path, evicted))
+    }
+
+    /// Writes the index to disk, if it has changed since it was last saved.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the combined size of every file stored in the cache, in bytes.
+    fn total_size(&self) -> u64 {
+        let mut seen = HashSet::default();
+        self.index
+            .entries
+            .values()
+            .filter(|entry| seen.insert(&entry.path))
+            .map(|entry| entry.size)
+            .sum()
+    }
+
+    /// Evicts the least recently used entries, except for `keep`, until the cache fits within its
+    /// maximum size.
+    fn evict(&mut self, keep: &Url) -> io::Result<Vec<Url>> {
+        let mut evicted = Vec::new();
+
+        while self.total_size() > self.max_size {
+            let lru = self
+                .index
+                .entries
+                .iter()
+                .filter(|(url, _)| *url != keep)
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(url, _)| url.clone());
+
+            match lru {
+                Some(url) => {
+                    let entry = self.index.entries.remove(&url).expect("entry exists");
+                    self.remove_if_unreferenced(&entry.path)?;
+                    evicted.push(url);
+                }
+                None => break,
+            }
+        }
+
+        Ok(evicted)
+    }
+
+    /// Deletes the file at `rel_path` if no entries in the index refer to it anymore.
+    fn remove_if_unreferenced(&self, rel_path: &Path) -> io::Result<()> {
+        if self.index.entries.values().any(|e| e.path == rel_path) {
+            return Ok(());
+        }
+
+        match fs::remove_file(self.dir.join(rel_path)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Atomically replaces the index file on disk.
+    fn save(&mut self) -> io::Result<()> {
+        let temp_path = self.dir.join(INDEX_TEMP_FILE_NAME);
+        let json = serde_json::to_vec(&self.index)?;
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, self.dir.join(INDEX_FILE_NAME))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Maps every cached URL to its file on disk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    /// Monotonic counter which is incremented on every cache access.
+    clock: u64,
+    entries: HashMap<Url, IndexEntry>,
+}
+
+/// Metadata for a single cached URL.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    /// Location of the file, relative to the cache directory.
+    path: PathBuf,
+    /// Size of the file, in bytes.
+    size: u64,
+    /// Value of [`Index::clock`] when this entry was last accessed.
+    last_access: u64,
+    /// HTTP caching metadata for the file.
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+/// Returns the content-addressed location of a file, relative to the cache directory.
+///
+/// Files are sharded into subdirectories by the first byte of their hash to avoid creating a
+/// single directory with a huge number of entries. Files whose hash collides with that of
+/// `collisions` other files are stored under a numbered suffix.
+fn object_path(hash: u64, collisions: u32) -> PathBuf {
+    let hex = format!("{:016x}", hash);
+    let file_name = match collisions {
+        0 => hex[2..].to_owned(),
+        n => format!("{}-{}", &hex[2..], n),
+    };
+
+    Path::new(OBJECTS_DIR_NAME).join(&hex[..2]).join(file_name)
+}
+
+/// Returns `true` if the stored file at `path` contains the same `size` bytes as the file at
+/// `other`.
+fn has_contents(path: &Path, other: &Path, size: u64) -> io::Result<bool> {
+    if fs::metadata(path)?.len() != size {
+        return Ok(false);
+    }
+
+    Ok(fs::read(path)? == fs::read(other)?)
+}
+
+/// Returns the current system time, in seconds since the UNIX epoch.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+    use std::io::Write;
+
+    use fnv::FnvHasher;
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn stage(cache: &DiskCache, contents: &[u8]) -> Download {
+        let mut file = tempfile::NamedTempFile::new_in(cache.temp_dir()).unwrap();
+        file.write_all(contents).unwrap();
+
+        let mut hasher = FnvHasher::default();
+        hasher.write(contents);
+
+        Download {
+            path: file.into_temp_path(),
+            hash: hasher.finish(),
+            size: contents.len() as u64,
+            metadata: Metadata::default(),
+        }
+    }
+
+    #[test]
+    fn persists_between_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let url: Url = "http://example.com/a.json".parse().unwrap();
+
+        {
+            let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+            let download = stage(&cache, b"hello");
+            cache.insert(&url, download).unwrap();
+        }
+
+        let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+        let (path, _) = cache.get(&url).expect("entry should survive a restart");
+        assert_eq!(fs::read(path).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn persists_metadata_between_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let url: Url = "http://example.com/a.json".parse().unwrap();
+        let metadata = Metadata {
+            etag: Some("\"abc\"".into()),
+            last_modified: None,
+            max_age: Some(60),
+            fetched_at: 1234,
+        };
+
+        {
+            let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+            let download = stage(&cache, b"hello");
+            cache.insert(&url, download).unwrap();
+            cache.set_metadata(&url, metadata.clone());
+            cache.flush().unwrap();
+        }
+
+        let cache = DiskCache::open(dir.path(), 1024).unwrap();
+        assert_eq!(cache.metadata(&url), Some(&metadata));
+    }
+
+    #[test]
+    fn parses_response_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=300"),
+        );
+
+        let metadata = Metadata::from_headers(&headers);
+        assert_eq!(metadata.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(
+            metadata.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert_eq!(metadata.max_age, Some(300));
+        assert!(!metadata.is_stale());
+
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        assert!(Metadata::from_headers(&headers).is_stale());
+    }
+
+    #[test]
+    fn merges_not_modified_metadata() {
+        let stale = Metadata {
+            etag: Some("\"abc\"".into()),
+            last_modified: None,
+            max_age: Some(60),
+            fetched_at: 0,
+        };
+
+        assert!(stale.is_stale());
+        let fresh = Metadata::from_headers(&HeaderMap::new()).merge(&stale);
+        assert_eq!(fresh.etag, stale.etag);
+        assert_eq!(fresh.max_age, Some(60));
+        assert!(!fresh.is_stale());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = DiskCache::open(dir.path(), 10).unwrap();
+        let a: Url = "http://example.com/a".parse().unwrap();
+        let b: Url = "http://example.com/b".parse().unwrap();
+        let c: Url = "http://example.com/c".parse().unwrap();
+
+        let download = stage(&cache, b"aaaa");
+        cache.insert(&a, download).unwrap();
+        let download = stage(&cache, b"bbbb");
+        cache.insert(&b, download).unwrap();
+
+        // Touch `a` so that `b` becomes the least recently used entry.
+        assert!(cache.get(&a).is_some());
+
+        let download = stage(&cache, b"cccc");
+        let (_, evicted) = cache.insert(&c, download).unwrap();
+        assert_eq!(evicted, vec![b.clone()]);
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&c).is_some());
+    }
+
+    #[test]
+    fn deduplicates_identical_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+        let a: Url = "http://example.com/a".parse().unwrap();
+        let b: Url = "http://example.com/b".parse().unwrap();
+
+        let download = stage(&cache, b"same");
+        let (path_a, _) = cache.insert(&a, download).unwrap();
+        let download = stage(&cache, b"same");
+        let (path_b, _) = cache.insert(&b, download).unwrap();
+
+        assert_eq!(path_a, path_b);
+        assert_eq!(cache.total_size(), 4);
+    }
+
+    #[test]
+    fn stores_colliding_contents_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let a: Url = "http://example.com/a".parse().unwrap();
+        let b: Url = "http://example.com/b".parse().unwrap();
+        let c: Url = "http://example.com/c".parse().unwrap();
+
+        {
+            let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+            let download = stage(&cache, b"aaaa");
+            let hash = download.hash;
+            let (path_a, _) = cache.insert(&a, download).unwrap();
+
+            // Pretend that different contents of the same size hash to the same value.
+            let mut download = stage(&cache, b"bbbb");
+            download.hash = hash;
+            let (path_b, _) = cache.insert(&b, download).unwrap();
+            assert_ne!(path_a, path_b);
+
+            let mut download = stage(&cache, b"bbbb");
+            download.hash = hash;
+            let (path_c, _) = cache.insert(&c, download).unwrap();
+            assert_eq!(path_b, path_c);
+            assert_eq!(cache.total_size(), 8);
+        }
+
+        let mut cache = DiskCache::open(dir.path(), 1024).unwrap();
+        let (path, _) = cache.get(&a).unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"aaaa");
+        let (path, _) = cache.get(&b).unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"bbbb");
+    }
+}
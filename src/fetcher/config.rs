@@ -0,0 +1,178 @@
+//! Configuration options for the background fetcher.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Proxy;
+
+use super::{RetryPolicy, Timeouts, Transport};
+
+/// Configuration options passed to [`spawn()`](super::spawn()).
+///
+/// By default, downloaded files are cached in the OS temporary directory and deleted as soon as
+/// the [`Fetcher`](super::Fetcher) is dropped, and failed downloads are retried according to the
+/// default [`RetryPolicy`]. Files are downloaded over the network using an
+/// [`HttpTransport`](super::HttpTransport), with up to 8 downloads in flight at the same time.
+///
+/// Connecting to a server times out after 10 seconds, and waiting for the response headers or the
+/// next chunk of the body times out after 30 seconds. There is no limit on the total duration of a
+/// request by default, since large files may legitimately take a long time to download.
+///
+/// Files fetched into memory are kept there until their combined size exceeds 32 MiB, at which
+/// point the least recently used ones are evicted.
+#[derive(Clone, Debug)]
+pub struct FetcherConfig {
+    pub(super) persistent_cache: Option<PersistentCache>,
+    pub(super) retry_policy: RetryPolicy,
+    pub(super) transport: Option<Arc<dyn Transport>>,
+    pub(super) max_concurrent_downloads: usize,
+    pub(super) max_memory_size: u64,
+    pub(super) timeouts: Timeouts,
+    pub(super) connect_timeout: Option<Duration>,
+    pub(super) user_agent: Option<HeaderValue>,
+    pub(super) default_headers: HeaderMap,
+    pub(super) proxy: Option<Proxy>,
+}
+
+impl FetcherConfig {
+    /// Creates a new `FetcherConfig` with the default settings.
+    #[inline]
+    pub fn new() -> Self {
+        FetcherConfig::default()
+    }
+
+    /// Caches downloaded files in `dir` so that they survive in between runs of the application.
+    ///
+    /// Once the combined size of the cached files exceeds `max_size` bytes, the least recently
+    /// used files are evicted from the cache until it fits within the limit again.
+    ///
+    /// This setting is not enabled by default.
+    #[inline]
+    pub fn with_persistent_cache<P: Into<PathBuf>>(mut self, dir: P, max_size: u64) -> Self {
+        self.persistent_cache = Some(PersistentCache {
+            dir: dir.into(),
+            max_size,
+        });
+        self
+    }
+
+    /// Sets the policy which determines whether and when failed downloads are retried.
+    #[inline]
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sets the backend which is used to retrieve files, e.g. a [`FileTransport`] for running
+    /// entirely offline or a [`MemoryTransport`] for testing.
+    ///
+    /// The connect timeout, user agent, default headers and proxy only apply to the default
+    /// [`HttpTransport`](super::HttpTransport), so they are ignored if a transport is set here.
+    ///
+    /// [`FileTransport`]: super::FileTransport
+    /// [`MemoryTransport`]: super::MemoryTransport
+    #[inline]
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets the maximum number of downloads which may be in flight at the same time.
+    ///
+    /// Further downloads are queued and started in order of their [`Priority`] as soon as
+    /// another download finishes. The limit is at least 1.
+    ///
+    /// [`Priority`]: super::Priority
+    #[inline]
+    pub fn with_max_concurrent_downloads(mut self, max: usize) -> Self {
+        self.max_concurrent_downloads = max.max(1);
+        self
+    }
+
+    /// Sets the maximum combined size in bytes of the files which are kept in memory by
+    /// [`Fetcher::fetch_bytes()`](super::Fetcher::fetch_bytes()) and
+    /// [`Fetcher::fetch_json()`](super::Fetcher::fetch_json()).
+    ///
+    /// Once the limit is exceeded, the least recently used files are evicted from memory, and
+    /// fetched again the next time they are requested.
+    #[inline]
+    pub fn with_max_memory_size(mut self, max_size: u64) -> Self {
+        self.max_memory_size = max_size;
+        self
+    }
+
+    /// Sets the maximum time to wait for a connection to the server to be established.
+    #[inline]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time to wait for the response headers, and for each chunk of the response
+    /// body after that.
+    #[inline]
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time a request may take in total, including receiving the response body.
+    ///
+    /// Individual URLs can be given different timeouts with
+    /// [`Fetcher::set_timeouts()`](super::Fetcher::set_timeouts()).
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.total = Some(timeout);
+        self
+    }
+
+    /// Sets the value of the `User-Agent` header sent with every request.
+    #[inline]
+    pub fn with_user_agent(mut self, user_agent: HeaderValue) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    /// Adds a header which is sent with every request.
+    #[inline]
+    pub fn with_default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Sends every request through the given proxy server.
+    #[inline]
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+}
+
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        FetcherConfig {
+            persistent_cache: None,
+            retry_policy: RetryPolicy::default(),
+            transport: None,
+            max_concurrent_downloads: 8,
+            max_memory_size: 32 * 1024 * 1024,
+            timeouts: Timeouts {
+                read: Some(Duration::from_secs(30)),
+                total: None,
+            },
+            connect_timeout: Some(Duration::from_secs(10)),
+            user_agent: None,
+            default_headers: HeaderMap::new(),
+            proxy: None,
+        }
+    }
+}
+
+/// Location and size limit of a persistent on-disk cache.
+#[derive(Clone, Debug)]
+pub(super) struct PersistentCache {
+    pub dir: PathBuf,
+    pub max_size: u64,
+}
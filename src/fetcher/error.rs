@@ -0,0 +1,107 @@
+//! Error type returned by the background fetcher.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+
+/// An error which occurred while fetching a file.
+///
+/// This type is cheap to clone, since the underlying errors are reference counted.
+#[derive(Clone, Debug)]
+pub enum FetchError {
+    /// The server responded with a non-successful HTTP status code, e.g. `404 Not Found`.
+    Status(StatusCode),
+    /// The request could not be sent, or the connection failed while receiving the response.
+    Transport(Arc<reqwest::Error>),
+    /// The downloaded file could not be written to or read from disk.
+    Io(Arc<io::Error>),
+    /// The downloaded file could not be decoded.
+    Decode(Arc<serde_json::Error>),
+    /// The server took too long to respond, or to send the rest of the response body.
+    Timeout,
+}
+
+impl FetchError {
+    /// Returns `true` if the server responded with `404 Not Found` or `410 Gone`.
+    #[inline]
+    pub fn is_not_found(&self) -> bool {
+        match *self {
+            FetchError::Status(status) => {
+                status == StatusCode::NOT_FOUND || status == StatusCode::GONE
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the HTTP status code sent by the server, if the error was caused by one.
+    #[inline]
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self {
+            FetchError::Status(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            FetchError::Status(status) => write!(f, "server responded with status {}", status),
+            FetchError::Transport(ref e) => write!(f, "network error: {}", e),
+            FetchError::Io(ref e) => write!(f, "I/O error: {}", e),
+            FetchError::Decode(ref e) => write!(f, "failed to decode file: {}", e),
+            FetchError::Timeout => f.write_str("request timed out"),
+        }
+    }
+}
+
+impl Error for FetchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            FetchError::Status(_) | FetchError::Timeout => None,
+            FetchError::Transport(ref e) => Some(&**e),
+            FetchError::Io(ref e) => Some(&**e),
+            FetchError::Decode(ref e) => Some(&**e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Transport(Arc::new(e))
+    }
+}
+
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(Arc::new(e))
+    }
+}
+
+impl From<serde_json::Error> for FetchError {
+    fn from(e: serde_json::Error) -> Self {
+        FetchError::Decode(Arc::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinguishes_not_found() {
+        let not_found = FetchError::Status(StatusCode::NOT_FOUND);
+        assert!(not_found.is_not_found());
+        assert_eq!(not_found.status(), Some(StatusCode::NOT_FOUND));
+
+        let server_error = FetchError::Status(StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!server_error.is_not_found());
+
+        let io_error = FetchError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(!io_error.is_not_found());
+        assert_eq!(io_error.status(), None);
+    }
+}
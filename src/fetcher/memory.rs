@@ -0,0 +1,496 @@
+//! Downloads which are kept in memory rather than saved to disk.
+
+use std::future::Future;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use fnv::FnvHashMap as HashMap;
+use futures_util::StreamExt;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use url::Url;
+
+use super::cache::Metadata;
+use super::{
+    start_downloads, validators, Completion, Decoder, FetchError, Job, Payload, Priority, Progress,
+    Response, TransportResult, Worker,
+};
+
+/// The status of a download which is kept in memory.
+#[derive(Debug)]
+pub(super) enum MemoryStatus {
+    /// The file is queued or being downloaded. If a stale copy exists, it is kept here while it is
+    /// being revalidated.
+    Pending {
+        stale: Option<MemoryFile>,
+        attempts: u32,
+    },
+    /// The previous attempt failed, and the download is waiting to be retried.
+    CoolingDown {
+        stale: Option<MemoryFile>,
+        attempts: u32,
+        retry_at: Instant,
+    },
+    /// The file has finished downloading, successfully or not.
+    Ready(Result<MemoryFile, FetchError>),
+}
+
+impl MemoryStatus {
+    /// Returns the number of bytes of file contents held by this entry.
+    fn size(&self) -> u64 {
+        let file = match self {
+            MemoryStatus::Pending { stale, .. } | MemoryStatus::CoolingDown { stale, .. } => {
+                stale.as_ref()
+            }
+            MemoryStatus::Ready(result) => result.as_ref().ok(),
+        };
+
+        file.map_or(0, |file| file.bytes.len() as u64)
+    }
+}
+
+/// The contents of a downloaded file, along with its caching metadata.
+#[derive(Debug)]
+pub(super) struct MemoryFile {
+    bytes: Bytes,
+    metadata: Metadata,
+    /// Whether the file was just downloaded or revalidated and has not been handed out yet.
+    validated: bool,
+    /// When the file was last handed out, for evicting the least recently used files first.
+    last_used: u64,
+}
+
+/// The current download status of a file which is kept in memory.
+pub(super) type BytesResponse = Response<Bytes>;
+
+/// The files which are kept in memory, keyed by their URLs.
+///
+/// Once the combined size of the finished files exceeds the maximum size, the least recently used
+/// ones are evicted. Evicted files are loaded from the persistent cache or downloaded again the
+/// next time they are requested.
+#[derive(Debug)]
+pub(super) struct MemoryCache {
+    entries: HashMap<Url, MemoryStatus>,
+    size: u64,
+    max_size: u64,
+    /// Incremented every time a file is handed out.
+    clock: u64,
+}
+
+impl MemoryCache {
+    /// Creates a new empty `MemoryCache` holding up to `max_size` bytes of finished files.
+    pub fn new(max_size: u64) -> Self {
+        MemoryCache {
+            entries: HashMap::default(),
+            size: 0,
+            max_size,
+            clock: 0,
+        }
+    }
+
+    /// Returns the status of `url`, if it has been requested before.
+    #[inline]
+    pub fn get(&self, url: &Url) -> Option<&MemoryStatus> {
+        self.entries.get(url)
+    }
+
+    /// Inserts or replaces the status of `url`.
+    fn insert(&mut self, url: Url, status: MemoryStatus) {
+        self.size += status.size();
+        if let Some(previous) = self.entries.insert(url, status) {
+            self.size -= previous.size();
+        }
+    }
+
+    /// Removes the status of `url` and returns it.
+    fn remove(&mut self, url: &Url) -> Option<MemoryStatus> {
+        let status = self.entries.remove(url)?;
+        self.size -= status.size();
+        Some(status)
+    }
+
+    /// Marks `file` as the most recently used file.
+    fn touch(&mut self, file: &mut MemoryFile) {
+        self.clock += 1;
+        file.last_used = self.clock;
+    }
+
+    /// Evicts the least recently used finished files until the cache fits within its maximum
+    /// size again. `keep` is never evicted, so that a single large file is not downloaded over
+    /// and over again.
+    fn evict(&mut self, keep: &Url) {
+        while self.size > self.max_size {
+            let lru = self
+                .entries
+                .iter()
+                .filter(|(url, _)| *url != keep)
+                .filter_map(|(url, status)| match status {
+                    MemoryStatus::Ready(Ok(file)) => Some((url, file.last_used)),
+                    _ => None,
+                })
+                .min_by_key(|&(_, last_used)| last_used)
+                .map(|(url, _)| url.clone());
+
+            match lru {
+                Some(url) => drop(self.remove(&url)),
+                None => return,
+            }
+        }
+    }
+}
+
+/// Returns the contents of `url`, queueing a new download if it has not been requested before or
+/// its previous copy has expired.
+///
+/// Downloads into memory share the concurrency limit, prioritization, cancellation and retry
+/// behavior of downloads to disk. New downloads are queued with the given priority.
+pub(super) fn process_bytes(url: Url, priority: Priority, worker: &Rc<Worker>) -> BytesResponse {
+    let job = Job::Memory(url.clone());
+    let (stale, attempts) = {
+        let mut memory = worker.memory.borrow_mut();
+        match memory.remove(&url) {
+            Some(MemoryStatus::Ready(Ok(mut file)))
+                if file.validated || !file.metadata.is_stale() =>
+            {
+                file.validated = false;
+                memory.touch(&mut file);
+                let response = Response::Ready(Ok(file.bytes.clone()));
+                memory.insert(url, MemoryStatus::Ready(Ok(file)));
+                return response;
+            }
+            Some(MemoryStatus::Ready(Err(e))) => {
+                let response = Response::Ready(Err(e.clone()));
+                memory.insert(url, MemoryStatus::Ready(Err(e)));
+                return response;
+            }
+            Some(entry @ MemoryStatus::Pending { .. })
+            | Some(entry @ MemoryStatus::CoolingDown { .. }) => {
+                memory.insert(url, entry);
+                return Response::Pending(worker.progress(&job));
+            }
+            Some(MemoryStatus::Ready(Ok(stale))) => (Some(stale), 0),
+            None => match load_persisted(&url, worker) {
+                Some(mut file) if !file.metadata.is_stale() => {
+                    memory.touch(&mut file);
+                    let response = Response::Ready(Ok(file.bytes.clone()));
+                    memory.insert(url.clone(), MemoryStatus::Ready(Ok(file)));
+                    memory.evict(&url);
+                    return response;
+                }
+                stale => (stale, 0),
+            },
+        }
+    };
+
+    let entry = MemoryStatus::Pending { stale, attempts };
+    worker.memory.borrow_mut().insert(url, entry);
+    worker.scheduler.borrow_mut().enqueue(job.clone(), priority);
+    start_downloads(worker);
+
+    Response::Pending(worker.progress(&job))
+}
+
+/// Starts downloading `url` into memory, if necessary, and sends a completion event containing its
+/// contents decoded with `decode` once it has finished.
+pub(super) fn subscribe_json(url: Url, decode: Decoder, priority: Priority, worker: &Rc<Worker>) {
+    match process_bytes(url.clone(), priority, worker) {
+        Response::Ready(result) => {
+            let result = result
+                .and_then(decode)
+                .map(|value| Payload::Json(value.into()));
+            worker.completions.send(Completion { url, result }).ok();
+        }
+        Response::Pending(_) => {
+            let job = Job::Memory(url.clone());
+            worker.scheduler.borrow_mut().set_priority(&job, priority);
+            worker.json_subscribers.borrow_mut().insert(url, decode);
+        }
+    }
+}
+
+/// Loads a copy of `url` which was saved to the persistent cache during a previous run, if any.
+fn load_persisted(url: &Url, worker: &Worker) -> Option<MemoryFile> {
+    let file = worker.cache.borrow_mut().lookup(url)?;
+    let bytes = std::fs::read(file.path()).ok()?;
+
+    Some(MemoryFile {
+        bytes: bytes.into(),
+        metadata: file.metadata,
+        validated: false,
+        last_used: 0,
+    })
+}
+
+/// Returns `true` if the in-memory copy of `url` has expired and must be revalidated.
+pub(super) fn is_stale(url: &Url, worker: &Worker) -> bool {
+    match worker.memory.borrow().get(url) {
+        Some(MemoryStatus::Ready(Ok(file))) => file.metadata.is_stale(),
+        _ => false,
+    }
+}
+
+/// Returns the time at which the failed in-memory download of `url` will be retried, if any.
+pub(super) fn retry_at(url: &Url, worker: &Worker) -> Option<Instant> {
+    match worker.memory.borrow().get(url) {
+        Some(MemoryStatus::CoolingDown { retry_at, .. }) => Some(*retry_at),
+        _ => None,
+    }
+}
+
+/// Forgets a failed in-memory download of `url`, so that the next request for it starts over.
+pub(super) fn forget_failure(url: &Url, worker: &Worker) {
+    let mut memory = worker.memory.borrow_mut();
+    if let Some(MemoryStatus::Ready(Err(_))) = memory.get(url) {
+        memory.remove(url);
+    }
+}
+
+/// Stops a queued, active or cooling down in-memory download of `url`.
+///
+/// If a stale copy was being revalidated, it is kept and revalidated again on the next request.
+pub(super) fn cancel(url: &Url, worker: &Worker) {
+    let job = Job::Memory(url.clone());
+    {
+        let mut scheduler = worker.scheduler.borrow_mut();
+        scheduler.remove(&job);
+
+        if let Some(download) = worker.active.borrow_mut().remove(&job) {
+            download.abort.abort();
+            scheduler.deactivate(&job, true);
+        }
+    }
+
+    worker.json_subscribers.borrow_mut().remove(url);
+
+    let mut memory = worker.memory.borrow_mut();
+    match memory.remove(url) {
+        Some(MemoryStatus::Pending { stale, .. })
+        | Some(MemoryStatus::CoolingDown { stale, .. }) => {
+            if let Some(stale) = stale {
+                memory.insert(url.clone(), MemoryStatus::Ready(Ok(stale)));
+            }
+        }
+        Some(entry) => memory.insert(url.clone(), entry),
+        None => {}
+    }
+}
+
+/// Downloads the pending file at `url` into memory.
+///
+/// Like downloads to disk, failed attempts are queued again once the download has cooled down,
+/// according to the [`RetryPolicy`](super::RetryPolicy).
+pub(super) async fn download_bytes(url: Url, worker: Rc<Worker>) {
+    let job = Job::Memory(url.clone());
+
+    // Stale copies are revalidated with a conditional request, just like files on disk.
+    let headers = match worker.memory.borrow().get(&url) {
+        Some(MemoryStatus::Pending {
+            stale: Some(stale), ..
+        }) => validators(&stale.metadata),
+        _ => HeaderMap::new(),
+    };
+
+    let report = |progress| worker.set_progress(&job, progress);
+    let result = read_body(worker.get(&url, headers), report).await;
+
+    worker.active.borrow_mut().remove(&job);
+    let delay = finish_bytes(&url, result, &worker);
+    worker
+        .scheduler
+        .borrow_mut()
+        .deactivate(&job, delay.is_none());
+    start_downloads(&worker);
+
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+
+        if resume_bytes(&url, &worker) {
+            worker.scheduler.borrow_mut().requeue(job);
+            start_downloads(&worker);
+        }
+    }
+}
+
+/// Stores the outcome of an in-memory download attempt, saving it to the persistent cache if
+/// enabled.
+///
+/// Returns the delay until the next attempt if the download failed but will be retried.
+fn finish_bytes(
+    url: &Url,
+    result: Result<(Option<Bytes>, Metadata), FetchError>,
+    worker: &Worker,
+) -> Option<Duration> {
+    let mut memory = worker.memory.borrow_mut();
+    let (stale, attempts) = match memory.remove(url) {
+        Some(MemoryStatus::Pending { stale, attempts }) => (stale, attempts + 1),
+        _ => (None, 1),
+    };
+
+    let result = match (result, stale) {
+        (Ok((Some(bytes), metadata)), _) => {
+            // Failing to persist the file is not fatal, since the copy in memory can still be used.
+            let mut cache = worker.cache.borrow_mut();
+            cache.persist(url, &bytes, metadata.clone()).ok();
+
+            Ok(MemoryFile {
+                bytes,
+                metadata,
+                validated: true,
+                last_used: 0,
+            })
+        }
+        (Ok((None, metadata)), Some(mut stale)) => {
+            // The contents have not changed, so only the metadata needs to be updated.
+            stale.metadata = metadata.merge(&stale.metadata);
+            stale.validated = true;
+
+            let mut cache = worker.cache.borrow_mut();
+            cache.refresh_metadata(url, stale.metadata.clone());
+            Ok(stale)
+        }
+        (Ok((None, _)), None) => Err(FetchError::Status(StatusCode::NOT_MODIFIED)),
+        (Err(error), stale) => match worker.policy.delay(&error, attempts) {
+            Some(delay) => {
+                let entry = MemoryStatus::CoolingDown {
+                    stale,
+                    attempts,
+                    retry_at: Instant::now() + delay,
+                };
+                memory.insert(url.clone(), entry);
+                return Some(delay);
+            }
+            None => Err(error),
+        },
+    };
+
+    let mut result = result;
+    if let Ok(ref mut file) = result {
+        memory.touch(file);
+    }
+
+    notify_subscriber(url, &mut result, worker);
+    memory.insert(url.clone(), MemoryStatus::Ready(result));
+    memory.evict(url);
+    None
+}
+
+/// Sends the decoded contents of a finished in-memory download of `url` to its subscriber, if any.
+///
+/// The contents are decoded on the background thread, so the subscriber never has to.
+fn notify_subscriber(url: &Url, result: &mut Result<MemoryFile, FetchError>, worker: &Worker) {
+    let decode = match worker.json_subscribers.borrow_mut().remove(url) {
+        Some(decode) => decode,
+        None => return,
+    };
+
+    let decoded = match result {
+        Ok(file) => {
+            file.validated = false;
+            decode(file.bytes.clone())
+        }
+        Err(e) => Err(e.clone()),
+    };
+
+    let result = decoded.map(|value| Payload::Json(value.into()));
+    let url = url.clone();
+    worker.completions.send(Completion { url, result }).ok();
+}
+
+/// Marks an in-memory download which has finished cooling down as pending again.
+///
+/// Returns `false` if the download is no longer cooling down and should not be resumed.
+fn resume_bytes(url: &Url, worker: &Worker) -> bool {
+    let mut memory = worker.memory.borrow_mut();
+    match memory.remove(url) {
+        Some(MemoryStatus::CoolingDown {
+            stale, attempts, ..
+        }) => {
+            memory.insert(url.clone(), MemoryStatus::Pending { stale, attempts });
+            true
+        }
+        Some(entry) => {
+            memory.insert(url.clone(), entry);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Awaits the response to a GET request and collects its body in memory. `report` is called with
+/// the progress after every chunk of the body.
+///
+/// Returns `None` in place of the body if the server responds with `304 Not Modified`.
+async fn read_body<F, R>(request: F, mut report: R) -> Result<(Option<Bytes>, Metadata), FetchError>
+where
+    F: Future<Output = TransportResult>,
+    R: FnMut(Progress),
+{
+    let response = request.await?;
+    let metadata = Metadata::from_headers(&response.headers);
+    match response.status {
+        StatusCode::NOT_MODIFIED => return Ok((None, metadata)),
+        status if !status.is_success() => return Err(FetchError::Status(status)),
+        _ => {}
+    }
+
+    let started = Instant::now();
+    let mut progress = Progress::from_headers(&response.headers);
+    report(progress);
+
+    let mut body = BytesMut::new();
+    let mut stream = response.body;
+    while let Some(chunk) = stream.next().await {
+        body.extend_from_slice(&chunk?);
+
+        progress.received = body.len() as u64;
+        progress.elapsed = started.elapsed();
+        report(progress);
+    }
+
+    Ok((Some(body.freeze()), metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready(size: usize) -> MemoryStatus {
+        MemoryStatus::Ready(Ok(MemoryFile {
+            bytes: vec![0; size].into(),
+            metadata: Metadata::from_headers(&HeaderMap::new()),
+            validated: false,
+            last_used: 0,
+        }))
+    }
+
+    #[test]
+    fn evicts_least_recently_used_files() {
+        let url = |path: &str| -> Url { format!("http://example.com/{}", path).parse().unwrap() };
+
+        let mut memory = MemoryCache::new(10);
+        for path in &["a", "b", "c"] {
+            let mut status = ready(4);
+            if let MemoryStatus::Ready(Ok(ref mut file)) = status {
+                memory.touch(file);
+            }
+
+            memory.insert(url(path), status);
+            memory.evict(&url(path));
+        }
+
+        // Adding "c" exceeded the limit, so "a" was evicted since it was used the longest ago.
+        assert!(memory.get(&url("a")).is_none());
+        assert!(memory.get(&url("b")).is_some());
+        assert_eq!(memory.size, 8);
+
+        // A file which exceeds the limit on its own is kept, but evicts everything else.
+        memory.insert(url("big"), ready(20));
+        memory.evict(&url("big"));
+        assert_eq!(memory.entries.len(), 1);
+        assert_eq!(memory.size, 20);
+
+        assert!(memory.remove(&url("big")).is_some());
+        assert_eq!(memory.size, 0);
+    }
+}
@@ -0,0 +1,94 @@
+//! Progress reporting for pending downloads.
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, CONTENT_LENGTH};
+
+/// The progress of a pending download.
+///
+/// Downloads which are still queued, or waiting for the response headers to arrive, report zero
+/// bytes received and an unknown total size.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// Number of bytes of the response body received so far.
+    pub received: u64,
+    /// Number of bytes received by earlier attempts, before an interrupted download was resumed.
+    ///
+    /// These are included in `received`, but not in the throughput of the current attempt.
+    pub resumed: u64,
+    /// Total size of the response body in bytes, if the server sent a `Content-Length` header.
+    pub total: Option<u64>,
+    /// Time elapsed between receiving the response headers and the most recent chunk of the body.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Creates a new `Progress` for a response which has not received any of its body yet.
+    pub(super) fn from_headers(headers: &HeaderMap) -> Self {
+        let total = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        Progress {
+            received: 0,
+            resumed: 0,
+            total,
+            elapsed: Duration::default(),
+        }
+    }
+
+    /// Returns the fraction of the response body received so far, between `0.0` and `1.0`.
+    ///
+    /// Returns `None` if the total size of the body is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.received as f64 / total as f64).min(1.0) as f32),
+            None => None,
+        }
+    }
+
+    /// Returns the average download speed of the current attempt so far, in bytes per second.
+    ///
+    /// Bytes received by earlier attempts of a resumed download are not taken into account.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.received.saturating_sub(self.resumed) as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn computes_fraction_and_throughput() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("2000"));
+
+        let mut progress = Progress::from_headers(&headers);
+        assert_eq!(progress.total, Some(2000));
+        assert_eq!(progress.fraction(), Some(0.0));
+        assert_eq!(progress.throughput(), 0.0);
+
+        progress.received = 500;
+        progress.elapsed = Duration::from_millis(250);
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(progress.throughput(), 2000.0);
+
+        // Only the bytes received since the download was resumed count towards the throughput.
+        progress.resumed = 250;
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(progress.throughput(), 1000.0);
+
+        let unknown = Progress::from_headers(&HeaderMap::new());
+        assert_eq!(unknown.fraction(), None);
+    }
+}
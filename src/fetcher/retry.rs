@@ -0,0 +1,179 @@
+//! Retry policy for failed downloads.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use super::FetchError;
+
+/// Determines whether and when failed downloads are retried.
+///
+/// Delays grow exponentially with every failed attempt, starting at the base delay and doubling
+/// each time up to the maximum delay. A random amount of jitter is subtracted from each delay so
+/// that many files failing at once are not all retried at exactly the same moment.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    is_retryable: fn(&FetchError) -> bool,
+}
+
+impl RetryPolicy {
+    /// Creates a new `RetryPolicy` with the default settings.
+    ///
+    /// By default, downloads are attempted up to 5 times, starting with a delay of 500 ms between
+    /// attempts up to a maximum of 30 seconds, with up to 50% jitter. See [`is_transient()`] for
+    /// the errors which are retried by default.
+    #[inline]
+    pub fn new() -> Self {
+        RetryPolicy::default()
+    }
+
+    /// Never retries failed downloads.
+    #[inline]
+    pub fn never() -> Self {
+        RetryPolicy::default().with_max_attempts(1)
+    }
+
+    /// Sets the maximum number of download attempts, including the first one.
+    #[inline]
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry, which doubles after every failed attempt.
+    #[inline]
+    pub fn with_base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the upper limit for the delay between attempts.
+    #[inline]
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Sets the fraction of each delay, between `0.0` and `1.0`, which may be randomly shaved off.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `jitter` is outside of the range `0.0..=1.0`.
+    #[inline]
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&jitter),
+            "jitter must be within 0.0..=1.0"
+        );
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the predicate which decides whether a download which failed with an error is retried.
+    #[inline]
+    pub fn with_retryable(mut self, is_retryable: fn(&FetchError) -> bool) -> Self {
+        self.is_retryable = is_retryable;
+        self
+    }
+
+    /// Returns how long to wait before retrying a download which has failed `attempts` times.
+    ///
+    /// Returns `None` if the error is not retryable or the maximum number of attempts is reached.
+    pub(super) fn delay(&self, error: &FetchError, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts || !(self.is_retryable)(error) {
+            return None;
+        }
+
+        Some(self.backoff(attempts, random_fraction()))
+    }
+
+    /// Computes the jittered delay after `attempts` failures, given a random number in `[0, 1)`.
+    fn backoff(&self, attempts: u32, random: f64) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+
+        delay.mul_f64(1.0 - self.jitter * random)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            is_retryable: is_transient,
+        }
+    }
+}
+
+/// Returns `true` if the error is likely to go away on its own.
+///
+/// This includes network and I/O errors, timeouts, `408 Request Timeout`, `429 Too Many Requests`,
+/// and all `5xx` server errors. Other status codes, such as `404 Not Found`, and decoding errors
+/// are not considered transient.
+pub fn is_transient(error: &FetchError) -> bool {
+    match *error {
+        FetchError::Status(status) => {
+            status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        FetchError::Transport(_) | FetchError::Io(_) | FetchError::Timeout => true,
+        FetchError::Decode(_) => false,
+    }
+}
+
+/// Returns a random number in the range `[0, 1)`.
+///
+/// Every `RandomState` is seeded with fresh random keys, which is plenty for jitter and saves us
+/// from pulling in a dedicated random number generator.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially() {
+        let policy = RetryPolicy::new()
+            .with_base_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(5));
+
+        assert_eq!(policy.backoff(1, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3, 0.0), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4, 0.0), Duration::from_secs(5));
+        assert_eq!(policy.backoff(100, 0.0), Duration::from_secs(5));
+        assert_eq!(policy.backoff(2, 0.5), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn retries_transient_errors_only() {
+        let policy = RetryPolicy::new().with_max_attempts(3);
+        let io_error = FetchError::from(io::Error::from(io::ErrorKind::ConnectionReset));
+        let server_error = FetchError::Status(StatusCode::SERVICE_UNAVAILABLE);
+        let not_found = FetchError::Status(StatusCode::NOT_FOUND);
+
+        assert!(policy.delay(&io_error, 1).is_some());
+        assert!(policy.delay(&server_error, 2).is_some());
+        assert!(policy.delay(&server_error, 3).is_none());
+        assert!(policy.delay(&not_found, 1).is_none());
+        assert!(RetryPolicy::never().delay(&io_error, 1).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! Prioritized scheduling of downloads with a concurrency limit.
+
+use std::hash::Hash;
+
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use url::Url;
+
+/// The priority of a download.
+///
+/// When the number of concurrent downloads is limited, queued downloads with higher priorities
+/// are started first. Downloads are started in no particular order when priorities are equal.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Priority(pub i32);
+
+impl Priority {
+    /// The lowest possible priority.
+    pub const LOWEST: Priority = Priority(i32::MIN);
+    /// The highest possible priority.
+    pub const HIGHEST: Priority = Priority(i32::MAX);
+}
+
+/// Decides which queued downloads to start next, while keeping the number of active downloads
+/// below a limit.
+///
+/// Downloads are identified by keys of type `K`, which default to their URLs.
+#[derive(Debug)]
+pub(super) struct Scheduler<K = Url> {
+    max_active: usize,
+    active: usize,
+    queued: HashSet<K>,
+    priorities: HashMap<K, Priority>,
+}
+
+impl<K: Clone + Eq + Hash> Scheduler<K> {
+    /// Creates a new `Scheduler` which runs up to `max_active` downloads at the same time.
+    pub fn new(max_active: usize) -> Self {
+        Scheduler {
+            max_active,
+            active: 0,
+            queued: HashSet::default(),
+            priorities: HashMap::default(),
+        }
+    }
+
+    /// Queues a download of `key` with the given priority.
+    pub fn enqueue(&mut self, key: K, priority: Priority) {
+        self.priorities.insert(key.clone(), priority);
+        self.queued.insert(key);
+    }
+
+    /// Queues a download of `key` again after a failed attempt, keeping its previous priority.
+    pub fn requeue(&mut self, key: K) {
+        let priority = self.priorities.get(&key).copied().unwrap_or_default();
+        self.enqueue(key, priority);
+    }
+
+    /// Changes the priority of an unfinished download. Unknown keys are ignored.
+    pub fn set_priority(&mut self, key: &K, priority: Priority) {
+        if let Some(p) = self.priorities.get_mut(key) {
+            *p = priority;
+        }
+    }
+
+    /// Removes the highest priority download from the queue and marks it as active.
+    ///
+    /// Returns `None` if the queue is empty or too many downloads are active already.
+    pub fn next(&mut self) -> Option<K> {
+        if self.active >= self.max_active {
+            return None;
+        }
+
+        let priorities = &self.priorities;
+        let key = self
+            .queued
+            .iter()
+            .max_by_key(|key| priorities.get(*key).copied().unwrap_or_default())?
+            .clone();
+
+        self.queued.remove(&key);
+        self.active += 1;
+        Some(key)
+    }
+
+    /// Marks an active download as no longer active.
+    ///
+    /// If `finished` is `false`, the download will be requeued later, so its priority is kept.
+    pub fn deactivate(&mut self, key: &K, finished: bool) {
+        self.active = self.active.saturating_sub(1);
+        if finished {
+            self.priorities.remove(key);
+        }
+    }
+
+    /// Forgets a cancelled download which is not active, i.e. it is either queued or cooling down.
+    pub fn remove(&mut self, key: &K) {
+        self.priorities.remove(key);
+        self.queued.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse("http://example.com")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn starts_highest_priority_first() {
+        let mut scheduler = Scheduler::new(2);
+        scheduler.enqueue(url("low"), Priority(-5));
+        scheduler.enqueue(url("high"), Priority(10));
+        scheduler.enqueue(url("medium"), Priority(0));
+
+        assert_eq!(scheduler.next(), Some(url("high")));
+        scheduler.set_priority(&url("low"), Priority::HIGHEST);
+        assert_eq!(scheduler.next(), Some(url("low")));
+
+        // Only two downloads may be active at a time.
+        assert_eq!(scheduler.next(), None);
+        scheduler.deactivate(&url("high"), true);
+        assert_eq!(scheduler.next(), Some(url("medium")));
+        assert_eq!(scheduler.next(), None);
+    }
+
+    #[test]
+    fn removes_queued_downloads() {
+        let mut scheduler = Scheduler::new(1);
+        scheduler.enqueue(url("a"), Priority::default());
+        scheduler.enqueue(url("b"), Priority::LOWEST);
+
+        assert_eq!(scheduler.next(), Some(url("a")));
+        scheduler.remove(&url("b"));
+        scheduler.deactivate(&url("a"), true);
+        assert_eq!(scheduler.next(), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Timeouts which keep stalled requests from staying pending forever.
+
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::future::{FutureExt, LocalBoxFuture};
+use futures_util::stream::{self, StreamExt};
+use tokio::time::Instant;
+
+use super::{FetchError, TransportResult};
+
+/// Limits on how long a single request may take. A limit of `None` means there is no limit.
+///
+/// Requests which exceed either limit fail with [`FetchError::Timeout`], and are retried according
+/// to the [`RetryPolicy`](super::RetryPolicy) like any other transient error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timeouts {
+    /// Maximum time to wait for the response headers, and for each chunk of the body after that.
+    pub read: Option<Duration>,
+    /// Maximum time for the entire request, from sending it to receiving the end of the body.
+    pub total: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Returns the instant at which the next read times out, given the deadline for the entire
+    /// request.
+    fn next_deadline(&self, deadline: Option<Instant>) -> Option<Instant> {
+        let read = self.read.map(|read| Instant::now() + read);
+        match (read, deadline) {
+            (Some(read), Some(deadline)) => Some(read.min(deadline)),
+            (read, deadline) => read.or(deadline),
+        }
+    }
+}
+
+/// Wraps a request sent through a [`Transport`](super::Transport) so that it fails with
+/// [`FetchError::Timeout`] as soon as one of the given timeouts elapses.
+pub(super) fn with_timeouts(
+    request: LocalBoxFuture<'static, TransportResult>,
+    timeouts: Timeouts,
+) -> LocalBoxFuture<'static, TransportResult> {
+    if timeouts == Timeouts::default() {
+        return request;
+    }
+
+    async move {
+        let deadline = timeouts.total.map(|total| Instant::now() + total);
+        let mut response = within(timeouts.next_deadline(deadline), request).await??;
+
+        // The body ends right after the first error, so a timed out read is never retried.
+        let body = response.body;
+        response.body = stream::unfold(Some(body), move |body| async move {
+            let mut body = body?;
+            match within(timeouts.next_deadline(deadline), body.next()).await {
+                Ok(Some(chunk)) => Some((chunk, Some(body))),
+                Ok(None) => None,
+                Err(error) => Some((Err(error), None)),
+            }
+        })
+        .boxed_local();
+
+        Ok(response)
+    }
+    .boxed_local()
+}
+
+/// Awaits `future`, failing with [`FetchError::Timeout`] if it has not completed by `deadline`.
+async fn within<F: Future>(deadline: Option<Instant>, future: F) -> Result<F::Output, FetchError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future)
+            .await
+            .map_err(|_| FetchError::Timeout),
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures_util::future;
+    use reqwest::StatusCode;
+
+    use super::super::TransportResponse;
+    use super::*;
+
+    #[tokio::test]
+    async fn times_out_stalled_requests() {
+        let timeouts = Timeouts {
+            read: Some(Duration::from_millis(10)),
+            total: None,
+        };
+
+        let stalled = with_timeouts(future::pending().boxed_local(), timeouts);
+        assert!(matches!(stalled.await, Err(FetchError::Timeout)));
+
+        let mut response = TransportResponse::empty(StatusCode::OK);
+        let chunk = stream::once(future::ok(Bytes::from("hello")));
+        response.body = chunk.chain(stream::pending()).boxed_local();
+
+        let mut body = with_timeouts(future::ok(response).boxed_local(), timeouts)
+            .await
+            .expect("response headers timed out")
+            .body;
+        assert_eq!(body.next().await.unwrap().unwrap(), "hello");
+        assert!(matches!(body.next().await, Some(Err(FetchError::Timeout))));
+        assert!(body.next().await.is_none());
+    }
+}
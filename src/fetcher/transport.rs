@@ -0,0 +1,390 @@
+//! Backends which actually retrieve the files requested from the fetcher.
+
+use std::fmt::Debug;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use fnv::FnvHashMap as HashMap;
+use futures_util::future::{FutureExt, LocalBoxFuture};
+use futures_util::stream::{self, LocalBoxStream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, ETAG, IF_NONE_MATCH};
+use reqwest::{Client, StatusCode};
+use url::Url;
+
+use super::{FetchError, FetcherConfig};
+
+/// Size of the chunks in which local files are streamed, in bytes.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A backend which sends GET requests on behalf of the [`Fetcher`](super::Fetcher).
+///
+/// Transports are executed on the background thread, so the futures they return do not need to be
+/// `Send`. The default transport is [`HttpTransport`].
+pub trait Transport: Debug + Send + Sync {
+    /// Sends a GET request for `url` with the given extra headers.
+    ///
+    /// The returned future resolves once the response headers are available, and the response
+    /// body is streamed afterwards. Non-successful status codes should be returned as responses
+    /// rather than errors, so the fetcher can decide how to handle them.
+    fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult>;
+}
+
+/// The result of sending a request through a [`Transport`].
+pub type TransportResult = Result<TransportResponse, FetchError>;
+
+/// A response received from a [`Transport`].
+pub struct TransportResponse {
+    /// The HTTP status code of the response.
+    pub status: StatusCode,
+    /// The HTTP response headers.
+    pub headers: HeaderMap,
+    /// A stream of chunks making up the response body.
+    pub body: LocalBoxStream<'static, Result<Bytes, FetchError>>,
+}
+
+impl TransportResponse {
+    /// Creates a new response with the given status code and an empty body.
+    pub fn empty(status: StatusCode) -> Self {
+        TransportResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: stream::empty().boxed_local(),
+        }
+    }
+
+    /// Creates a new `200 OK` response containing the given bytes.
+    ///
+    /// The `Content-Length` header is filled in automatically.
+    pub fn from_bytes<B: Into<Bytes>>(mut headers: HeaderMap, body: B) -> Self {
+        let body = body.into();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+
+        TransportResponse {
+            status: StatusCode::OK,
+            headers,
+            body: stream::once(async { Ok(body) }).boxed_local(),
+        }
+    }
+}
+
+impl Debug for TransportResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TransportResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// Fetches files from the Internet using HTTP(S).
+#[derive(Clone, Debug, Default)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    /// Creates a new `HttpTransport` with a default HTTP client.
+    #[inline]
+    pub fn new() -> Self {
+        HttpTransport::default()
+    }
+
+    /// Creates a new `HttpTransport` which sends requests using the given HTTP client.
+    #[inline]
+    pub fn with_client(client: Client) -> Self {
+        HttpTransport { client }
+    }
+
+    /// Creates a new `HttpTransport` with the connect timeout, user agent, default headers and
+    /// proxy from the given configuration.
+    ///
+    /// Fails if the HTTP client could not be initialized, e.g. because of an invalid proxy.
+    pub fn from_config(config: &FetcherConfig) -> Result<Self, FetchError> {
+        let mut builder = Client::builder().default_headers(config.default_headers.clone());
+
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(ref user_agent) = config.user_agent {
+            builder = builder.user_agent(user_agent.clone());
+        }
+
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+
+        Ok(HttpTransport::with_client(builder.build()?))
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+        let request = self.client.get(url.as_str()).headers(headers);
+
+        async move {
+            let response = request.send().await?;
+            Ok(TransportResponse {
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response
+                    .bytes_stream()
+                    .map(|chunk| chunk.map_err(FetchError::from))
+                    .boxed_local(),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+/// Reads files from the local file system.
+///
+/// `file://` URLs are always read directly from their paths. If a root directory is specified,
+/// all other URLs are read from `<root>/<host>/<path>` as well, which allows a mirror of a remote
+/// server to be served from disk. Missing files result in `404 Not Found` responses.
+#[derive(Clone, Debug, Default)]
+pub struct FileTransport {
+    root: Option<PathBuf>,
+}
+
+impl FileTransport {
+    /// Creates a new `FileTransport` which only serves `file://` URLs.
+    #[inline]
+    pub fn new() -> Self {
+        FileTransport::default()
+    }
+
+    /// Creates a new `FileTransport` which serves remote URLs from the given directory.
+    #[inline]
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        FileTransport {
+            root: Some(root.into()),
+        }
+    }
+
+    /// Returns the local path corresponding to `url`, if any.
+    fn local_path(&self, url: &Url) -> Option<PathBuf> {
+        if url.scheme() == "file" {
+            return url.to_file_path().ok();
+        }
+
+        let mut path = self.root.as_ref()?.join(url.host_str()?);
+        for segment in url.path_segments()? {
+            // Refuse to escape the root directory through `..` segments.
+            if !segment.is_empty() && segment != "." && segment != ".." {
+                path.push(segment);
+            }
+        }
+
+        Some(path)
+    }
+}
+
+impl Transport for FileTransport {
+    fn get(&self, url: &Url, _headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+        use tokio::io::AsyncReadExt;
+
+        let path = self.local_path(url);
+
+        async move {
+            let path = match path {
+                Some(path) => path,
+                None => return Ok(TransportResponse::empty(StatusCode::NOT_FOUND)),
+            };
+
+            let file = match tokio::fs::File::open(&path).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    return Ok(TransportResponse::empty(StatusCode::NOT_FOUND))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let mut headers = HeaderMap::new();
+            if let Ok(metadata) = file.metadata().await {
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+            }
+
+            let chunks = stream::unfold(Some(file), |file| async move {
+                let mut file = file?;
+                let mut buffer = vec![0; FILE_CHUNK_SIZE];
+                match file.read(&mut buffer).await {
+                    Ok(0) => None,
+                    Ok(len) => {
+                        buffer.truncate(len);
+                        Some((Ok(Bytes::from(buffer)), Some(file)))
+                    }
+                    Err(e) => Some((Err(e.into()), None)),
+                }
+            });
+
+            Ok(TransportResponse {
+                status: StatusCode::OK,
+                headers,
+                body: chunks.boxed_local(),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+/// Serves files from an in-memory map of URLs, which is mostly useful for testing.
+///
+/// Files may be tagged with an `ETag`, in which case conditional requests are answered with
+/// `304 Not Modified` as appropriate. Clones of a `MemoryTransport` share the same files, so they
+/// can be changed after the transport has been handed to the fetcher. Unknown URLs result in
+/// `404 Not Found` responses.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTransport {
+    inner: Arc<Mutex<MemoryFiles>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryFiles {
+    files: HashMap<Url, MemoryFile>,
+    requests: usize,
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl MemoryTransport {
+    /// Creates a new empty `MemoryTransport`.
+    #[inline]
+    pub fn new() -> Self {
+        MemoryTransport::default()
+    }
+
+    /// Serves `body` at the given URL, replacing any previous file.
+    pub fn insert<B: Into<Bytes>>(&self, url: Url, body: B) {
+        self.insert_response(url, StatusCode::OK, HeaderMap::new(), body);
+    }
+
+    /// Serves a response with an arbitrary status code and headers at the given URL.
+    pub fn insert_response<B: Into<Bytes>>(
+        &self,
+        url: Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: B,
+    ) {
+        let file = MemoryFile {
+            status,
+            headers,
+            body: body.into(),
+        };
+
+        self.inner.lock().unwrap().files.insert(url, file);
+    }
+
+    /// Returns the total number of requests received so far.
+    pub fn requests(&self) -> usize {
+        self.inner.lock().unwrap().requests
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.requests += 1;
+
+        let response = match inner.files.get(url) {
+            Some(file) if is_not_modified(&file.headers, &headers) => {
+                let mut response = TransportResponse::empty(StatusCode::NOT_MODIFIED);
+                response.headers = file.headers.clone();
+                response
+            }
+            Some(file) => {
+                let mut response =
+                    TransportResponse::from_bytes(file.headers.clone(), file.body.clone());
+                response.status = file.status;
+                response
+            }
+            None => TransportResponse::empty(StatusCode::NOT_FOUND),
+        };
+
+        async move { Ok(response) }.boxed_local()
+    }
+}
+
+/// Returns `true` if the `If-None-Match` request header matches the `ETag` of the response.
+fn is_not_modified(response: &HeaderMap, request: &HeaderMap) -> bool {
+    match (response.get(ETAG), request.get(IF_NONE_MATCH)) {
+        (Some(etag), Some(if_none_match)) => etag == if_none_match,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_mirrored_files_from_root() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("example.com").join("sets");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), b"{}").unwrap();
+
+        let transport = FileTransport::with_root(root.path());
+        let url: Url = "https://example.com/sets/a.json".parse().unwrap();
+        let response = transport.get(&url, HeaderMap::new()).await.unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+
+        let body: Vec<Bytes> = response.body.try_collect().await.unwrap();
+        assert_eq!(body.concat(), b"{}");
+
+        let missing: Url = "https://example.com/sets/b.json".parse().unwrap();
+        let response = transport.get(&missing, HeaderMap::new()).await.unwrap();
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+        let escape: Url = "https://example.com/../../etc/passwd".parse().unwrap();
+        let path = transport.local_path(&escape).unwrap();
+        assert!(path.starts_with(root.path()));
+    }
+
+    #[tokio::test]
+    async fn configures_http_client() {
+        use reqwest::header::HeaderName;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        // Answer a single request, and hand back its headers for inspection.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: Url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request[..len]).to_lowercase()
+        });
+
+        let config = FetcherConfig::new()
+            .with_connect_timeout(Duration::from_secs(5))
+            .with_user_agent(HeaderValue::from_static("dss-menu-test"))
+            .with_default_header(
+                HeaderName::from_static("x-test"),
+                HeaderValue::from_static("1"),
+            );
+        let transport = HttpTransport::from_config(&config).expect("invalid configuration");
+        let response = transport.get(&url, HeaderMap::new()).await.unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+
+        let request = server.join().unwrap();
+        assert!(request.contains("user-agent: dss-menu-test"));
+        assert!(request.contains("x-test: 1"));
+    }
+}
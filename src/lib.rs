@@ -3,4 +3,6 @@
 pub mod app;
 pub mod fetcher;
 pub mod menu;
+pub mod parental;
 pub mod schema;
+pub mod snapshot;
@@ -1,15 +1,79 @@
 //! Main entry point for the application.
 
-use anyhow::Error;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Error};
+use reqwest::header::HeaderValue;
+
 use dss_menu::app::App;
-use dss_menu::fetcher;
-use dss_menu::menu::{Menu, WidgetKind};
+use dss_menu::fetcher::{self, FetcherConfig};
+use dss_menu::menu::{Menu, WidgetKind, HOME_JSON_URL};
+use dss_menu::parental::{ParentalControls, RatingSystems};
+use dss_menu::snapshot::{self, SnapshotTransport};
 
 const WINDOW_WIDTH: u32 = 1920;
 const WINDOW_HEIGHT: u32 = 1080;
 const WINDOW_TITLE: &str = "Disney Streaming Services";
 
+/// Environment variable which, if set, enables the persistent on-disk download cache.
+const CACHE_DIR_VAR: &str = "DSS_MENU_CACHE_DIR";
+const CACHE_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Environment variable which, if set to `1`, starts the menu in kids mode.
+const KIDS_MODE_VAR: &str = "DSS_MENU_KIDS_MODE";
+
+/// Environment variable containing the maximum rating per rating system, e.g. `TVPG=TV-PG,MPAA=PG`.
+const MAX_RATINGS_VAR: &str = "DSS_MENU_MAX_RATINGS";
+/// Environment variable containing the PIN which reveals restricted tiles.
+const PIN_VAR: &str = "DSS_MENU_PIN";
+/// Environment variable pointing to a JSON file which replaces the built-in rating systems.
+const RATING_SYSTEMS_VAR: &str = "DSS_MENU_RATING_SYSTEMS";
+
+const USAGE: &str = "\
+USAGE:
+    dss-menu                      Runs the menu against the live DSS API
+    dss-menu --snapshot <DIR>     Runs the menu offline from a recorded snapshot
+    dss-menu record <DIR>         Records a snapshot of the live DSS API into <DIR>";
+
+/// A command passed on the command line.
+enum Command {
+    /// Runs the menu, optionally from a snapshot directory instead of the live API.
+    Run { snapshot: Option<PathBuf> },
+    /// Records a snapshot of the live API into a directory.
+    Record { dir: PathBuf },
+}
+
 fn main() -> anyhow::Result<()> {
+    match parse_args()? {
+        Command::Run { snapshot } => run(snapshot),
+        Command::Record { dir } => record(dir),
+    }
+}
+
+fn parse_args() -> anyhow::Result<Command> {
+    let mut args = std::env::args_os().skip(1);
+    let command = match args.next() {
+        None => Command::Run { snapshot: None },
+        Some(arg) if arg == "--snapshot" => Command::Run {
+            snapshot: Some(args.next().map(PathBuf::from).ok_or_else(usage)?),
+        },
+        Some(arg) if arg == "record" => Command::Record {
+            dir: args.next().map(PathBuf::from).ok_or_else(usage)?,
+        },
+        Some(_) => return Err(usage()),
+    };
+
+    match args.next() {
+        Some(_) => Err(usage()),
+        None => Ok(command),
+    }
+}
+
+fn usage() -> Error {
+    anyhow!("invalid arguments\n\n{}", USAGE)
+}
+
+fn run(snapshot: Option<PathBuf>) -> anyhow::Result<()> {
     let context = sdl2::init().map_err(Error::msg)?;
     let video_sys = context.video().map_err(Error::msg)?;
 
@@ -18,10 +82,94 @@ fn main() -> anyhow::Result<()> {
         .position_centered()
         .build()?;
 
-    let fetcher = fetcher::spawn();
+    let mut config = fetcher_config();
+    let mut home_url = HOME_JSON_URL.parse()?;
+
+    // Snapshots must never be mixed with live files from the persistent cache.
+    if let Some(dir) = snapshot {
+        let transport = SnapshotTransport::open(dir)?;
+        if let Some(url) = transport.home_url() {
+            home_url = url.clone();
+        }
+        config = config.with_transport(transport);
+    } else if let Some(cache_dir) = std::env::var_os(CACHE_DIR_VAR) {
+        config = config.with_persistent_cache(cache_dir, CACHE_MAX_SIZE);
+    }
+
+    let fetcher = fetcher::spawn(config)?;
     let (width, height) = window.size();
     let root_widget = WidgetKind::new_root(width, height);
-    App::new(Menu::new(fetcher), root_widget)
+    let menu = Menu::new(fetcher)
+        .with_home_url(home_url)
+        .with_kids_mode(std::env::var(KIDS_MODE_VAR).as_deref() == Ok("1"))
+        .with_parental_controls(parental_controls()?);
+    App::new(menu, root_widget)
         .with_error_message_box(WINDOW_TITLE)
         .run(context, window)
 }
+
+/// Returns the fetcher configuration shared by all commands.
+fn fetcher_config() -> FetcherConfig {
+    let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+    FetcherConfig::new().with_user_agent(HeaderValue::from_static(user_agent))
+}
+
+/// Reads the parental control profile from the environment.
+fn parental_controls() -> anyhow::Result<ParentalControls> {
+    let systems = match std::env::var_os(RATING_SYSTEMS_VAR) {
+        Some(path) => RatingSystems::load(path)?,
+        None => RatingSystems::default(),
+    };
+
+    let mut limits = Vec::new();
+    if let Ok(var) = std::env::var(MAX_RATINGS_VAR) {
+        for limit in var.split(',').filter(|limit| !limit.trim().is_empty()) {
+            let mut parts = limit.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                // A limit which is missing from the ordering would restrict every rating.
+                (Some(system), Some(max_rating))
+                    if systems.rank(system.trim(), max_rating.trim()).is_some() =>
+                {
+                    limits.push((system.trim().to_owned(), max_rating.trim().to_owned()));
+                }
+                (Some(_), Some(_)) => {
+                    return Err(anyhow!(
+                        "unknown rating system or rating {:?} in {}",
+                        limit,
+                        MAX_RATINGS_VAR
+                    ))
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "invalid rating limit {:?} in {}",
+                        limit,
+                        MAX_RATINGS_VAR
+                    ))
+                }
+            }
+        }
+    }
+
+    let mut controls = ParentalControls::new(systems);
+    for (system, max_rating) in limits {
+        controls = controls.with_limit(system, max_rating);
+    }
+
+    if let Ok(pin) = std::env::var(PIN_VAR) {
+        controls = controls.with_pin(pin);
+    }
+
+    Ok(controls)
+}
+
+fn record(dir: PathBuf) -> anyhow::Result<()> {
+    let fetcher = fetcher::spawn(fetcher_config())?;
+    let home_url = HOME_JSON_URL.parse()?;
+    let manifest = snapshot::record(&fetcher, &home_url, &dir)?;
+    println!(
+        "Recorded {} files into {}",
+        manifest.files.len(),
+        dir.display()
+    );
+    Ok(())
+}
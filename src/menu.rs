@@ -1,10 +1,13 @@
 //! Business logic for the application.
 
+use std::cell::{Cell, RefCell};
+use std::hash::Hasher;
 use std::path::PathBuf;
 use std::rc::Rc;
-use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
+use fnv::{FnvHashMap as HashMap, FnvHasher};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
@@ -12,14 +15,46 @@ use sdl2::rect::Rect;
 use sdl2::render::Texture;
 use sdl2::ttf::FontStyle;
 use url::Url;
-
-use crate::app::{Action, Context, Fullscreen, Properties, State, Widget, WidgetId, Widgets};
-use crate::fetcher::Fetcher;
+use uuid::Uuid;
+
+use crate::app::{
+    Action, Context, Easing, Fullscreen, Properties, State, Transition, Widget, WidgetId, Widgets,
+};
+use crate::fetcher::{
+    is_transient, Completion, FetchError, Fetcher, Payload, Priority, Response, Timeouts,
+};
+use crate::parental::ParentalControls;
 use crate::schema::{self, Set};
 
-const HOME_JSON_URL: &str = "https://cd-static.bamgrid.com/dp-117731241344/home.json";
+use self::details::{Content, Details};
+use self::pin::PinPrompt;
+
+mod details;
+mod pin;
+
+/// URL of the live `home.json` document from which the menu is built by default.
+pub const HOME_JSON_URL: &str = "https://cd-static.bamgrid.com/dp-117731241344/home.json";
+const REF_SET_URL: &str = "https://cd-static.bamgrid.com/dp-117731241344/sets/";
+/// How often to check whether the cached copy of `home.json` has expired.
+const HOME_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// The menu cannot be shown until `home.json` has loaded, so its requests fail fast.
+const HOME_TIMEOUTS: Timeouts = Timeouts {
+    read: Some(Duration::from_secs(5)),
+    total: Some(Duration::from_secs(15)),
+};
+
+/// Ref set rows are fetched once the cursor comes within this many rows of them.
+const LAZY_LOAD_DISTANCE: usize = 2;
+/// The next page of a row is fetched once the cursor comes within this many tiles of its end.
+const PAGINATION_DISTANCE: usize = 4;
+/// Thumbnail downloads are cancelled once the cursor moves further than this many rows away.
+const CANCEL_DISTANCE: usize = 3;
+/// Each row between a tile and the cursor lowers its download priority by this much, whereas each
+/// column only lowers it by one, so that thumbnails are loaded row by row outwards from the cursor.
+const ROW_PRIORITY_STRIDE: usize = 1000;
 
 const BACKGROUND_COLOR: Color = Color::RGB(7, 27, 15);
+const KIDS_BACKGROUND_COLOR: Color = Color::RGB(28, 58, 138);
 const RIGHT_MARGIN: i32 = 52;
 const TOP_MARGIN: i32 = 68;
 
@@ -31,7 +66,23 @@ const FONT_STYLE: FontStyle = FontStyle::NORMAL;
 const LABEL_POINT_SIZE: u16 = 42;
 const LABEL_PADDING: u32 = 18;
 
+const LOADING_MESSAGE: &str = "Loading...";
+const HOME_ERROR_MESSAGE: &str = "The menu could not be loaded. Press Enter to try again.";
+const NO_KIDS_CONTENT_MESSAGE: &str = "There is nothing to watch in kids mode. Press K to exit.";
+/// Number of pages fetched in a row without any tiles to show, after which the row is collapsed
+/// rather than paginated any further.
+const MAX_EMPTY_PAGES: u32 = 4;
+/// Number of placeholder rows displayed on the loading screen.
+const SKELETON_ROWS: u32 = 3;
+
+const ERROR_POINT_SIZE: u16 = 28;
+const ROW_ERROR_MESSAGE: &str = "This row could not be loaded.";
+const ROW_NOT_FOUND_MESSAGE: &str = "This row is no longer available.";
+
 const TILE_COLOR: Color = Color::RGB(23, 126, 127);
+const KIDS_TILE_COLOR: Color = Color::RGB(255, 176, 46);
+/// Tiles restricted by parental controls are displayed in this color instead of their thumbnail.
+const LOCKED_TILE_COLOR: Color = Color::RGB(40, 48, 56);
 const TILE_IMAGE_NAME: &str = "tile";
 const TILE_ASPECT_RATIO: &str = "1.78";
 const TILE_WIDTH: u32 = 500;
@@ -39,23 +90,170 @@ const TILE_HEIGHT: u32 = 281;
 const TILE_MARGIN: u32 = 28;
 
 const ROW_HEIGHT: u32 = TILE_HEIGHT + 156;
+/// Tiles keep their textures while they are within this many pixels of the window, i.e. one tile
+/// on either side, so that they are ready to be drawn as soon as the row scrolls.
+const VIRTUAL_ROW_MARGIN: u32 = TILE_WIDTH + TILE_MARGIN;
+
+const BADGE_BORDER_COLOR: Color = Color::WHITE;
+const BADGE_BORDER_WIDTH: u8 = 2;
 
 const CURSOR_BORDER_COLOR: Color = Color::WHITE;
 const CURSOR_BORDER_WIDTH: u8 = 10;
 const CURSOR_SCALE_FACTOR: f32 = 1.1;
 
+/// Tiles grow and shrink with this transition as they are selected and deselected.
+const SELECT_TRANSITION: Transition = Transition::new(Duration::from_millis(150), Easing::EaseOut);
+/// The menu grid and its rows scroll with this transition.
+const SCROLL_TRANSITION: Transition =
+    Transition::new(Duration::from_millis(250), Easing::EaseInOut);
+/// Thumbnails fade in with this transition once they have been downloaded.
+const FADE_IN_TRANSITION: Transition = Transition::new(Duration::from_millis(200), Easing::Linear);
+
+/// The colors in which the menu is drawn.
+#[derive(Clone, Copy, Debug)]
+struct Theme {
+    background: Color,
+    tile: Color,
+}
+
+const DEFAULT_THEME: Theme = Theme {
+    background: BACKGROUND_COLOR,
+    tile: TILE_COLOR,
+};
+
+/// A brighter theme used in kids mode.
+const KIDS_THEME: Theme = Theme {
+    background: KIDS_BACKGROUND_COLOR,
+    tile: KIDS_TILE_COLOR,
+};
+
 /// A zero or negative integer offset which marks how far to the right a grid row is scrolled.
 ///
 /// See the documentation for [`Menu::select_tile()`] for more.
 type ScrollOffset = isize;
 
+/// A single row of tiles in the menu grid.
+#[derive(Debug)]
+struct Row {
+    /// Label displaying the title of the row.
+    label: WidgetId,
+    /// Invisible anchor point to which every tile in the row is attached.
+    anchor: WidgetId,
+    /// How far the row has been scrolled horizontally by the user.
+    scroll_offset: ScrollOffset,
+    /// Whether the tiles in this row have been populated yet.
+    status: RowStatus,
+    /// The next page of tiles, if the row has not been fully populated yet.
+    next_page: Option<Page>,
+    /// Number of pages fetched so far in which none of the tiles are shown, e.g. because they
+    /// are filtered out in kids mode.
+    empty_pages: u32,
+    /// Details of the content behind every tile inserted so far, in order.
+    items: Vec<Content>,
+}
+
+/// A list of loading states for a [`Row`].
+#[derive(Debug)]
+enum RowStatus {
+    /// All tiles fetched so far have been inserted into the widget tree.
+    Loaded,
+    /// The row is a ref set which has not been requested yet.
+    Unloaded(Page),
+    /// The ref set, or the next page of the row, is currently being downloaded. Contains the offset
+    /// of the requested page, if any.
+    Loading(Option<u32>),
+    /// The ref set could not be retrieved, so an error message is displayed in its place.
+    Failed,
+    /// The row has no tiles to show, e.g. because all of them are filtered out in kids mode, so
+    /// the row is collapsed.
+    Empty,
+}
+
+/// A ref set, or a page of a curated set, which can be requested to populate a [`Row`].
+#[derive(Clone, Debug)]
+pub(crate) struct Page {
+    /// URL of the ref set or page.
+    pub url: Url,
+    /// Index of the first item expected on the page, or `None` if the first page of a ref set is
+    /// requested, whatever its offset.
+    pub offset: Option<u32>,
+}
+
+impl Row {
+    /// Returns `true` if the row will never contain any selectable tiles.
+    #[inline]
+    fn is_skipped(&self) -> bool {
+        matches!(self.status, RowStatus::Failed | RowStatus::Empty)
+    }
+
+    /// Returns `true` if the row has been collapsed, so it takes up no space in the menu grid.
+    #[inline]
+    fn is_collapsed(&self) -> bool {
+        matches!(self.status, RowStatus::Empty)
+    }
+}
+
+/// A list of loading states for the `home.json` document from which the menu grid is built.
+#[derive(Clone, Copy, Debug)]
+enum HomeStatus {
+    /// The document is being downloaded, and a loading screen is displayed in the meantime.
+    Loading,
+    /// The menu grid has been built from the document.
+    Loaded,
+    /// The document could not be downloaded or the grid could not be built from it, so an error
+    /// screen is displayed in its place.
+    Failed,
+}
+
+/// A part of the menu which is waiting for a download to complete.
+#[derive(Clone, Copy, Debug)]
+enum Subscriber {
+    /// The ref set or next page of the row at the given index.
+    Row(usize),
+    /// The thumbnail of the given tile widget, located at the given row/column of the grid.
+    Tile {
+        row: usize,
+        column: usize,
+        id: WidgetId,
+    },
+}
+
 /// Contains the state for the main menu.
 #[derive(Debug)]
 pub struct Menu {
     fetcher: Rc<Fetcher>,
-    rows: Vec<(WidgetId, ScrollOffset)>,
+    home_url: Url,
+    home_status: HomeStatus,
+    /// Digest of the `home.json` document from which the menu grid was built, used to detect
+    /// whether a refreshed copy has changed.
+    home_digest: u64,
+    /// Label which displays the loading and error messages while the menu grid is not shown.
+    status_label: WidgetId,
+    /// Invisible anchor point to which the placeholder tiles of the loading screen are attached.
+    skeleton: WidgetId,
+    rows: Vec<Row>,
+    subscribers: HashMap<Url, Vec<Subscriber>>,
+    /// Subscribers whose downloads were cancelled because they are too far away from the cursor,
+    /// or failed with a transient error. They are requested again once the cursor moves close
+    /// enough to them.
+    cancelled: HashMap<Url, Vec<Subscriber>>,
+    /// Completion events received by the details screen while the menu is paused, which are
+    /// dispatched once the menu resumes.
+    deferred: Rc<RefCell<Vec<Completion>>>,
     selected_tile: (usize, usize),
+    /// The tile which was selected when the download priorities were last updated.
+    prioritized_tile: (usize, usize),
     grid_root: WidgetId,
+    last_refresh_check: Instant,
+    refreshing_home: bool,
+    parental_controls: Rc<ParentalControls>,
+    /// Set by the PIN prompt once the parental control PIN has been entered correctly.
+    unlocked: Rc<Cell<bool>>,
+    /// Tiles restricted by parental controls, whose thumbnails are not downloaded until the
+    /// parental control PIN has been entered.
+    locked_tiles: Vec<(Url, Subscriber)>,
+    /// Only content marked as suitable for kids is shown in kids mode.
+    kids_mode: bool,
 }
 
 impl Menu {
@@ -64,22 +262,453 @@ impl Menu {
     pub fn new(f: Fetcher) -> Self {
         Menu {
             fetcher: Rc::new(f),
+            home_url: HOME_JSON_URL.parse().expect("home URL is valid"),
+            home_status: HomeStatus::Loading,
+            home_digest: 0,
+            status_label: WidgetId::root(),
+            skeleton: WidgetId::root(),
             rows: Vec::new(),
+            subscribers: HashMap::default(),
+            cancelled: HashMap::default(),
+            deferred: Rc::default(),
             selected_tile: (0, 0),
+            prioritized_tile: (0, 0),
             grid_root: WidgetId::root(),
+            last_refresh_check: Instant::now(),
+            refreshing_home: false,
+            parental_controls: Rc::new(ParentalControls::default()),
+            unlocked: Rc::new(Cell::new(false)),
+            locked_tiles: Vec::new(),
+            kids_mode: false,
         }
     }
 
-    /// Scrolls the entire menu one row up.
+    /// Builds the menu from the `home.json` document located at `url`.
+    ///
+    /// By default, the menu is built from [`HOME_JSON_URL`].
+    #[inline]
+    pub fn with_home_url(mut self, url: Url) -> Self {
+        self.home_url = url;
+        self
+    }
+
+    /// Starts the menu in kids mode, which only shows content marked as suitable for kids.
+    ///
+    /// Kids mode can be toggled at any time by pressing `K`. This setting is not enabled by
+    /// default.
+    #[inline]
+    pub fn with_kids_mode(mut self, enabled: bool) -> Self {
+        self.kids_mode = enabled;
+        self
+    }
+
+    /// Obscures tiles whose content is rated above the limits of the given parental controls.
+    ///
+    /// If the parental controls have a PIN, selecting an obscured tile prompts for it, and all
+    /// obscured tiles are revealed once it has been entered correctly.
+    #[inline]
+    pub fn with_parental_controls(mut self, controls: ParentalControls) -> Self {
+        self.parental_controls = Rc::new(controls);
+        self
+    }
+
+    /// Builds the menu grid once `home.json` has been downloaded, replacing the loading screen.
+    ///
+    /// If the document could not be downloaded or the grid could not be built from it, an error
+    /// screen is displayed instead, from which the user can try again.
+    fn load_home(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        let url = self.home_url.clone();
+        let home = match self.fetcher.poll_fetch_json::<schema::Home>(url) {
+            Response::Pending(_) => return,
+            Response::Ready(home) => home,
+        };
+
+        set_descendants_hidden(widgets, self.skeleton, true);
+        self.show_home(home.map_err(anyhow::Error::from), widgets);
+    }
+
+    /// Builds the menu grid from the `home.json` document, or displays the error screen if the
+    /// document could not be downloaded or the grid could not be built from it.
+    fn show_home(&mut self, home: anyhow::Result<schema::Home>, widgets: &mut Widgets<WidgetKind>) {
+        let result = home.and_then(|home| {
+            self.home_digest = digest_home(&home);
+
+            // This is the invisible anchor point to which the entire menu can be scrolled.
+            self.grid_root = widgets.insert(WidgetKind::new_anchor(0, 0), widgets.root());
+            let result = self.build_grid(&home, widgets);
+            if result.is_err() {
+                self.clear_grid(widgets);
+            }
+
+            result
+        });
+
+        match result {
+            Ok(()) => {
+                self.home_status = HomeStatus::Loaded;
+                self.last_refresh_check = Instant::now();
+
+                // Keep explaining why the menu is blank if kids mode filtered out every row.
+                let is_blank = self.kids_mode
+                    && !self.rows.is_empty()
+                    && self.rows.iter().all(Row::is_collapsed);
+                widgets.get_mut(self.status_label).set_hidden(!is_blank);
+            }
+            Err(_) => {
+                self.home_status = HomeStatus::Failed;
+                let message = HOME_ERROR_MESSAGE.to_owned();
+                widgets.get_mut(self.status_label).set_text(message);
+            }
+        }
+    }
+
+    /// Leaves the error screen and downloads `home.json` again.
+    fn retry_home(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        self.fetcher.forget_failure(self.home_url.clone());
+        self.show_loading_screen(widgets);
+    }
+
+    /// Displays the loading screen until the menu grid has been built from `home.json`.
+    fn show_loading_screen(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        self.home_status = HomeStatus::Loading;
+
+        let message = LOADING_MESSAGE.to_owned();
+        widgets.get_mut(self.status_label).set_text(message);
+        widgets.get_mut(self.status_label).set_hidden(false);
+        set_descendants_hidden(widgets, self.skeleton, false);
+    }
+
+    /// Switches kids mode on or off, and rebuilds the menu grid with the matching tiles and theme.
+    fn toggle_kids_mode(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        self.kids_mode = !self.kids_mode;
+
+        let theme = self.theme();
+        widgets.get_mut(widgets.root()).set_color(theme.background);
+        widgets
+            .get_mut(self.status_label)
+            .set_background(theme.background);
+        for &tile_id in widgets.get_children_of(self.skeleton) {
+            widgets.get_mut(tile_id).set_color(theme.tile);
+        }
+
+        // `home.json` is kept in memory, so the grid is rebuilt on the next frame.
+        if let HomeStatus::Loaded = self.home_status {
+            self.clear_grid(widgets);
+            self.show_loading_screen(widgets);
+        }
+    }
+
+    /// Returns the colors in which the menu is currently drawn.
+    #[inline]
+    fn theme(&self) -> Theme {
+        if self.kids_mode {
+            KIDS_THEME
+        } else {
+            DEFAULT_THEME
+        }
+    }
+
+    /// Creates a new label widget which matches the current theme.
+    fn new_label(&self, text: String, point_size: u16, x: i32, y: i32, width: u32) -> WidgetKind {
+        let mut label = WidgetKind::new_label(text, point_size, x, y, width);
+        label.set_background(self.theme().background);
+        label
+    }
+
+    /// Creates a new tile widget which matches the current theme.
+    fn new_tile(&self, x: i32, y: i32) -> WidgetKind {
+        let mut tile = WidgetKind::new_tile(x, y);
+        tile.set_color(self.theme().tile);
+        tile
+    }
+
+    /// Removes the menu grid and forgets about its rows, so it can be built again.
+    fn clear_grid(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        let _grid_root = widgets.remove(self.grid_root);
+        self.rows.clear();
+        self.subscribers.clear();
+        self.cancelled.clear();
+        self.locked_tiles.clear();
+        self.selected_tile = (0, 0);
+        self.prioritized_tile = (0, 0);
+    }
+
+    /// Inserts a row into the menu grid for every collection in the `home.json` document, and
+    /// selects the first tile.
+    fn build_grid(
+        &mut self,
+        home_menu: &schema::Home,
+        widgets: &mut Widgets<WidgetKind>,
+    ) -> anyhow::Result<()> {
+        let (max_width, _) = widgets.get(widgets.root()).bounds();
+        let rows = get_menu_rows(home_menu)?;
+        self.rows.reserve(rows.len());
+
+        for (i, row) in rows.iter().enumerate() {
+            let (label_id, label_x, label_y, label_height) = {
+                let title = get_row_title(row, i)?;
+
+                // Collapsed rows take up no space, so rows are positioned by their visual index.
+                let label = self.new_label(
+                    title.to_owned(),
+                    LABEL_POINT_SIZE,
+                    RIGHT_MARGIN,
+                    TOP_MARGIN + (self.visual_index(i) as u32 * ROW_HEIGHT) as i32,
+                    max_width,
+                );
+
+                // We affix labels to `grid_root` so that it can scroll up/down as the user presses
+                // `UP` and `DOWN`, but remains stationary when the user scrolls left/right.
+                let (x, y) = label.origin();
+                let (_, height) = label.bounds();
+                let label_id = widgets.insert(label, self.grid_root);
+
+                (label_id, x, y, height)
+            };
+
+            // This invisible anchor point is used to scroll the current row of tiles left/right
+            // independently of all the other rows. Tiles are placed directly below the label.
+            let tiles_y = label_y + (label_height + LABEL_PADDING) as i32;
+            let anchor = widgets.insert(WidgetKind::new_anchor(label_x, tiles_y), self.grid_root);
+            widgets.set_virtualized(anchor, Some(VIRTUAL_ROW_MARGIN));
+
+            let (status, next_page, items) = match &row.set {
+                Set::Curated { items, .. } => {
+                    let items = self.insert_tiles(i, anchor, items, widgets)?;
+                    match get_next_page(&row.set)? {
+                        // If none of the tiles on this page are shown, try the next page.
+                        Some(page) if items.is_empty() => (RowStatus::Unloaded(page), None, items),
+                        next_page => (RowStatus::Loaded, next_page, items),
+                    }
+                }
+                // Ref sets are only fetched once the cursor comes close enough to them.
+                Set::Ref { ref_id, .. } => {
+                    let url = get_ref_set_url(ref_id)?;
+                    let page = Page { url, offset: None };
+                    (RowStatus::Unloaded(page), None, Vec::new())
+                }
+            };
+
+            // Mark that the current row hasn't been scrolled horizontally by the user yet. This
+            // value comes in handy later in `select_tile()`.
+            let scroll_offset = 0;
+            let is_empty = matches!(status, RowStatus::Loaded) && items.is_empty();
+            self.rows.push(Row {
+                label: label_id,
+                anchor,
+                scroll_offset,
+                status,
+                next_page,
+                empty_pages: 0,
+                items,
+            });
+
+            // Increment the height of `grid_root` so that its dimensions include this row.
+            widgets.get_mut(self.grid_root).properties_mut().bounds.1 += ROW_HEIGHT;
+
+            if is_empty {
+                self.collapse_row(i, widgets);
+            }
+        }
+
+        // The menu grid is populated, so select the first tile which is not skipped over.
+        let (row, column) = self.selected_tile;
+        let row = (row..self.rows.len())
+            .find(|&i| !self.rows[i].is_skipped())
+            .unwrap_or(row);
+        self.selected_tile = (row, column);
+        self.select_tile(row, column, widgets);
+
+        Ok(())
+    }
+
+    /// Periodically revalidates `home.json` with the server once its cached copy has expired.
+    ///
+    /// Revalidation uses a conditional request, so the document is only downloaded again if it has
+    /// actually changed on the server. The document is decoded on the background thread.
+    ///
+    /// If the refreshed document differs from the one the menu grid was built from, the grid is
+    /// rebuilt from scratch and the first tile is selected again. If it could not be downloaded,
+    /// the current grid is kept.
+    fn refresh_home(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        if self.refreshing_home {
+            let url = self.home_url.clone();
+            if let Response::Ready(result) = self.fetcher.poll_fetch_json::<schema::Home>(url) {
+                self.refreshing_home = false;
+
+                match result {
+                    Ok(home) if digest_home(&home) != self.home_digest => {
+                        self.clear_grid(widgets);
+                        self.show_home(Ok(home), widgets);
+                    }
+                    _ => {}
+                }
+            }
+
+            return;
+        }
+
+        if self.last_refresh_check.elapsed() < HOME_REFRESH_INTERVAL {
+            return;
+        }
+
+        self.last_refresh_check = Instant::now();
+        self.refreshing_home = self.fetcher.is_stale(self.home_url.clone());
+    }
+
+    /// Starts downloading the file at `url`, notifying `subscriber` once the download finishes.
+    ///
+    /// Ref sets are decoded on the background thread, while thumbnails are saved to disk. Tiles
+    /// which are too far away from the cursor are not downloaded until it comes closer. If a
+    /// previous download of `url` failed, it is tried again.
+    fn subscribe(&mut self, url: Url, subscriber: Subscriber) {
+        if self.is_far_away(&subscriber) {
+            self.cancelled.entry(url).or_default().push(subscriber);
+            return;
+        }
+
+        let priority = self.priority(&subscriber);
+        let subscribers = self.subscribers.entry(url.clone()).or_default();
+        if subscribers.is_empty() {
+            // The fetcher remembers failed downloads, so they have to be forgotten to try again.
+            self.fetcher.forget_failure(url.clone());
+            match subscriber {
+                Subscriber::Row(_) => self.fetcher.subscribe_json::<schema::RefSet>(url, priority),
+                Subscriber::Tile { .. } => self.fetcher.subscribe(url, priority),
+            }
+        }
+
+        subscribers.push(subscriber);
+    }
+
+    /// Returns the download priority of a subscriber, based on its distance from the cursor.
+    fn priority(&self, subscriber: &Subscriber) -> Priority {
+        let (cur_row, cur_column) = self.selected_tile;
+        let (row, column) = match *subscriber {
+            Subscriber::Row(row) => (row, None),
+            Subscriber::Tile { row, column, .. } => (row, Some(column)),
+        };
+
+        // Rows scroll horizontally on their own, so find the column of the given row which is
+        // lined up with the cursor. Rows may still be in the middle of being initialized.
+        let scroll_offset = |i| self.rows.get(i).map_or(0, |r: &Row| r.scroll_offset);
+        let column_distance = column.map_or(0, |column| {
+            let focus = cur_column as isize + scroll_offset(cur_row) - scroll_offset(row);
+            distance(column, focus.max(0) as usize)
+        });
+
+        let distance = distance(row, cur_row)
+            .saturating_mul(ROW_PRIORITY_STRIDE)
+            .saturating_add(column_distance);
+        Priority(-(distance.min(i32::MAX as usize) as i32))
+    }
+
+    /// Returns `true` if the subscriber is a tile which is too far away from the cursor to load.
+    fn is_far_away(&self, subscriber: &Subscriber) -> bool {
+        let (cur_row, _) = self.selected_tile;
+        match *subscriber {
+            Subscriber::Tile { row, .. } => distance(row, cur_row) > CANCEL_DISTANCE,
+            Subscriber::Row(_) => false,
+        }
+    }
+
+    /// Updates the download priorities after the cursor has moved.
+    ///
+    /// Thumbnails which are now far away from the cursor are cancelled, and previously cancelled
+    /// thumbnails which have come close enough again are requested once more.
+    fn reprioritize(&mut self) {
+        self.prioritized_tile = self.selected_tile;
+
+        let mut cancelled = Vec::new();
+        for (url, subscribers) in &self.subscribers {
+            if subscribers.iter().all(|s| self.is_far_away(s)) {
+                cancelled.push(url.clone());
+            } else if let Some(priority) = subscribers.iter().map(|s| self.priority(s)).max() {
+                self.fetcher.set_priority(url.clone(), priority);
+            }
+        }
+
+        for url in cancelled {
+            self.fetcher.cancel(url.clone());
+            let subscribers = self.subscribers.remove(&url).unwrap_or_default();
+            self.cancelled.entry(url).or_default().extend(subscribers);
+        }
+
+        let resumed: Vec<Url> = self
+            .cancelled
+            .iter()
+            .filter(|(_, subscribers)| !subscribers.iter().all(|s| self.is_far_away(s)))
+            .map(|(url, _)| url.clone())
+            .collect();
+
+        for url in resumed {
+            for subscriber in self.cancelled.remove(&url).unwrap_or_default() {
+                self.subscribe(url.clone(), subscriber);
+            }
+        }
+    }
+
+    /// Dispatches every download which has finished since the last frame to its subscribers,
+    /// along with those handed back by the details screen while the menu was paused.
+    fn dispatch_completions(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        let mut completions = std::mem::take(&mut *self.deferred.borrow_mut());
+        completions.extend(self.fetcher.completions());
+
+        for Completion { url, result } in completions {
+            let subscribers = self.subscribers.remove(&url).unwrap_or_default();
+            for subscriber in subscribers {
+                match subscriber {
+                    Subscriber::Row(row) => self.load_row(row, result.clone(), widgets),
+                    Subscriber::Tile { id, .. } => {
+                        let mut tile = widgets.get_mut(id);
+                        let thumbnail = match result {
+                            Ok(Payload::File(ref path)) => {
+                                tile.set_opacity(0).animate_opacity(255, FADE_IN_TRANSITION);
+                                Thumbnail::Ready(path.clone())
+                            }
+                            Ok(Payload::Json(_)) => Thumbnail::Failed,
+                            Err(ref e) => {
+                                // Park thumbnails which may still load later, so that they are
+                                // requested again the next time the cursor moves.
+                                if is_transient(e) {
+                                    let parked = self.cancelled.entry(url.clone()).or_default();
+                                    parked.push(subscriber);
+                                }
+
+                                Thumbnail::Failed
+                            }
+                        };
+
+                        tile.set_thumbnail(thumbnail);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scrolls the entire menu one row up, skipping over rows which have no tiles to select.
     fn move_up(&mut self, widgets: &mut Widgets<WidgetKind>) {
         let (row, column) = self.selected_tile;
-        self.select_tile(row.saturating_sub(1), column, widgets);
+        if let Some(prev) = (0..row).rev().find(|&i| self.is_selectable(i, widgets)) {
+            self.select_tile(prev, column, widgets);
+        }
     }
 
-    /// Scrolls the entire menu one row down.
+    /// Scrolls the entire menu one row down, skipping over rows which have no tiles to select.
     fn move_down(&mut self, widgets: &mut Widgets<WidgetKind>) {
         let (row, column) = self.selected_tile;
-        self.select_tile(row + 1, column, widgets);
+        if let Some(next) = (row + 1..self.rows.len()).find(|&i| self.is_selectable(i, widgets)) {
+            self.select_tile(next, column, widgets);
+        }
+    }
+
+    /// Returns `true` if the cursor can move onto the given row.
+    ///
+    /// Besides rows which failed to load or are empty, this excludes rows whose ref set or first
+    /// page is still loading, since they have no tiles yet. They keep loading lazily around the
+    /// cursor and become selectable once their tiles arrive.
+    fn is_selectable(&self, row: usize, widgets: &Widgets<WidgetKind>) -> bool {
+        !self.rows[row].is_skipped() && !widgets.get_children_of(self.rows[row].anchor).is_empty()
     }
 
     /// Scrolls the current row one tile to the left.
@@ -97,19 +726,26 @@ impl Menu {
     /// Selects an arbitrary tile from the menu grid, given its row/column position.
     fn select_tile(&mut self, row: usize, column: usize, widgets: &mut Widgets<WidgetKind>) {
         let (cur_row, cur_column) = self.selected_tile;
-        let (cur_tile_id, cur_scroll_offset) = self.rows[cur_row];
+        let (cur_anchor_id, cur_scroll_offset) = match self.rows.get(cur_row) {
+            Some(cur) => (cur.anchor, cur.scroll_offset),
+            None => return,
+        };
 
-        if let Some((anchor_id, scroll_offset)) = self.rows.get(row).copied() {
+        if let Some((anchor_id, scroll_offset)) =
+            self.rows.get(row).map(|r| (r.anchor, r.scroll_offset))
+        {
             let tile_ids = widgets.get_children_of(anchor_id);
             let column = compute_tile_index(column, cur_scroll_offset, scroll_offset);
 
             if let Some(tile_id) = tile_ids.get(column) {
-                // Deselect the current tile and scale it down, if necessary.
+                // Deselect the current tile and scale it down, if necessary. The current row may
+                // still be empty if it is a ref set that hasn't finished loading yet.
                 let (delta_width, delta_height) = {
-                    let cur_tile_ids = widgets.get_children_of(cur_tile_id)[cur_column];
-                    let mut tile = widgets.get_mut(cur_tile_ids);
+                    let cur_tile_ids = widgets.get_children_of(cur_anchor_id);
+                    let cur_tile_id = cur_tile_ids.get(cur_column).unwrap_or(tile_id);
+                    let mut tile = widgets.get_mut(*cur_tile_id);
 
-                    let (width, height) = tile.bounds();
+                    let (width, height) = tile.target_bounds();
                     let new_width = (width as f32 * (1.0 / CURSOR_SCALE_FACTOR)) as u32;
                     let new_height = (height as f32 * (1.0 / CURSOR_SCALE_FACTOR)) as u32;
 
@@ -118,12 +754,12 @@ impl Menu {
 
                     // Confirm that this tile is actually scaled up before shrinking it back down.
                     if width != TILE_WIDTH && height != TILE_HEIGHT {
-                        tile.set_bounds(new_width, new_height);
+                        tile.animate_bounds(new_width, new_height, SELECT_TRANSITION);
 
-                        let (x, y) = tile.origin();
+                        let (x, y) = tile.target_origin();
                         let new_x = x + delta_width as i32 / 2;
                         let new_y = y + delta_height as i32 / 2;
-                        tile.set_origin(new_x, new_y);
+                        tile.animate_origin(new_x, new_y, SELECT_TRANSITION);
                     }
 
                     tile.clear_border();
@@ -135,57 +771,72 @@ impl Menu {
                 let (new_tile_x, new_tile_y) = {
                     let mut tile = widgets.get_mut(*tile_id);
 
-                    let (width, height) = tile.bounds();
-                    tile.set_bounds(width + delta_width, height + delta_height);
+                    let (width, height) = tile.target_bounds();
+                    let (new_width, new_height) = (width + delta_width, height + delta_height);
+                    tile.animate_bounds(new_width, new_height, SELECT_TRANSITION);
 
-                    let (x, y) = tile.origin();
+                    let (x, y) = tile.target_origin();
                     let new_x = x - delta_width as i32 / 2;
                     let new_y = y - delta_height as i32 / 2;
-                    tile.set_origin(new_x, new_y);
+                    tile.animate_origin(new_x, new_y, SELECT_TRANSITION);
 
                     tile.set_border(CURSOR_BORDER_COLOR, CURSOR_BORDER_WIDTH);
 
                     (new_x, new_y)
                 };
 
-                // Scroll the entire page up and down, if necessary.
+                // Scroll the entire page up and down, if necessary. Rows which failed to load are
+                // skipped over, so we may need to scroll by more than one row at a time. Collapsed
+                // rows take up no space, so they must not be scrolled past.
                 let (root_x, root_y) = widgets.get(widgets.root()).origin();
                 let (root_w, root_h) = widgets.get(widgets.root()).bounds();
+                let rows_between = distance(self.visual_index(cur_row), self.visual_index(row));
+                let scroll_height = rows_between as i32 * ROW_HEIGHT as i32;
 
                 if cur_row > row {
-                    let (_, grid_y) = widgets.get(self.grid_root).origin();
+                    let (_, grid_y) = widgets.get(self.grid_root).target_origin();
 
                     let should_scroll_up = new_tile_y + (TILE_HEIGHT as i32) < root_h as i32 / 2;
                     let is_not_first_row = grid_y < root_y;
 
                     if should_scroll_up && is_not_first_row {
-                        widgets.translate(self.grid_root, 0, ROW_HEIGHT as i32);
+                        widgets.translate(self.grid_root, 0, scroll_height, SCROLL_TRANSITION);
                     }
                 } else if cur_row < row {
                     let should_scroll_down = new_tile_y - TILE_HEIGHT as i32 > (root_h as i32) / 2;
 
                     if should_scroll_down {
-                        widgets.translate(self.grid_root, 0, -(ROW_HEIGHT as i32));
+                        widgets.translate(self.grid_root, 0, -scroll_height, SCROLL_TRANSITION);
                     }
                 }
 
                 // Scroll the current row left and right, if necessary.
                 if cur_column > column {
-                    let (anchor_x, _) = widgets.get(anchor_id).origin();
+                    let (anchor_x, _) = widgets.get(anchor_id).target_origin();
 
                     let should_scroll_left = new_tile_x < root_x as i32;
                     let is_not_first_column = anchor_x < root_x;
 
                     if should_scroll_left && is_not_first_column {
-                        widgets.translate(anchor_id, TILE_WIDTH as i32 + TILE_MARGIN as i32, 0);
-                        self.rows[cur_row].1 += (cur_column - column) as isize;
+                        widgets.translate(
+                            anchor_id,
+                            TILE_WIDTH as i32 + TILE_MARGIN as i32,
+                            0,
+                            SCROLL_TRANSITION,
+                        );
+                        self.rows[cur_row].scroll_offset += (cur_column - column) as isize;
                     }
                 } else if cur_column < column {
                     let should_scroll_right = new_tile_x + TILE_WIDTH as i32 > root_w as i32;
 
                     if should_scroll_right {
-                        widgets.translate(anchor_id, -(TILE_WIDTH as i32 + TILE_MARGIN as i32), 0);
-                        self.rows[cur_row].1 -= (column - cur_column) as isize;
+                        widgets.translate(
+                            anchor_id,
+                            -(TILE_WIDTH as i32 + TILE_MARGIN as i32),
+                            0,
+                            SCROLL_TRANSITION,
+                        );
+                        self.rows[cur_row].scroll_offset -= (column - cur_column) as isize;
                     }
                 }
 
@@ -193,6 +844,259 @@ impl Menu {
             }
         }
     }
+
+    /// Appends a row of tiles, whose thumbnails are loaded in asynchronously, to the given anchor
+    /// of the given row.
+    ///
+    /// Returns the details of the content behind the inserted tiles, or `Err` if any of the tiles
+    /// is missing a thumbnail image. In this case, no tiles are inserted at all.
+    fn insert_tiles(
+        &mut self,
+        row: usize,
+        anchor_id: WidgetId,
+        items: &[schema::Collection],
+        widgets: &mut Widgets<WidgetKind>,
+    ) -> anyhow::Result<Vec<Content>> {
+        let items: Vec<_> = items
+            .iter()
+            .filter(|item| !self.kids_mode || is_kids_available(item))
+            .collect();
+
+        let image_urls = items
+            .iter()
+            .map(|item| get_tile_image_url(item))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let num_tiles = widgets.get_children_of(anchor_id).len();
+
+        for (j, (image_url, item)) in image_urls.into_iter().zip(&items).enumerate() {
+            let column = num_tiles + j;
+            let (x, y) = get_tile_origin(&widgets.get(anchor_id), column);
+            let mut tile = self.new_tile(x, y);
+
+            let locked = !self.unlocked.get() && !self.parental_controls.allows(item.ratings());
+            if locked {
+                tile.set_color(LOCKED_TILE_COLOR);
+            }
+
+            let id = widgets.insert(tile, anchor_id);
+            let subscriber = Subscriber::Tile { row, column, id };
+            if locked {
+                self.locked_tiles.push((image_url.clone(), subscriber));
+            } else {
+                self.subscribe(image_url.clone(), subscriber);
+            }
+        }
+
+        Ok(items.into_iter().map(Content::from_collection).collect())
+    }
+
+    /// Opens the details screen for the currently selected tile, if any.
+    ///
+    /// If the tile is restricted by parental controls, the PIN prompt is opened instead.
+    fn open_details(&self) -> Action<WidgetKind> {
+        let (row, column) = self.selected_tile;
+        if self.is_locked(row, column) {
+            if !self.parental_controls.has_pin() {
+                return Action::Continue;
+            }
+
+            let controls = self.parental_controls.clone();
+            let prompt = PinPrompt::new(controls, self.unlocked.clone());
+            return Action::Push(Box::new(prompt));
+        }
+
+        match self.rows.get(row).and_then(|r| r.items.get(column)) {
+            Some(content) => {
+                let deferred = self.deferred.clone();
+                let details = Details::new(self.fetcher.clone(), content.clone(), deferred);
+                Action::Push(Box::new(details))
+            }
+            None => Action::Continue,
+        }
+    }
+
+    /// Returns `true` if the tile at the given row/column is restricted by parental controls.
+    fn is_locked(&self, row: usize, column: usize) -> bool {
+        self.locked_tiles
+            .iter()
+            .any(|(_, subscriber)| match *subscriber {
+                Subscriber::Tile {
+                    row: r, column: c, ..
+                } => r == row && c == column,
+                Subscriber::Row(_) => false,
+            })
+    }
+
+    /// Reveals all tiles restricted by parental controls and starts downloading their thumbnails.
+    fn unlock_tiles(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        for (url, subscriber) in std::mem::take(&mut self.locked_tiles) {
+            if let Subscriber::Tile { id, .. } = subscriber {
+                widgets.get_mut(id).set_color(self.theme().tile);
+            }
+
+            self.subscribe(url, subscriber);
+        }
+    }
+
+    /// Starts downloading the ref set or next page of the given row.
+    fn request_row(&mut self, row: usize, page: Page) {
+        self.rows[row].status = RowStatus::Loading(page.offset);
+        self.subscribe(page.url, Subscriber::Row(row));
+    }
+
+    /// Appends the tiles from a downloaded ref set or page to the given row.
+    fn load_row(
+        &mut self,
+        row: usize,
+        result: Result<Payload, FetchError>,
+        widgets: &mut Widgets<WidgetKind>,
+    ) {
+        let anchor_id = self.rows[row].anchor;
+        let was_empty = widgets.get_children_of(anchor_id).is_empty();
+        let requested_offset = match self.rows[row].status {
+            RowStatus::Loading(offset) => offset,
+            _ => None,
+        };
+
+        let result = result
+            .map_err(anyhow::Error::from)
+            .and_then(|payload| {
+                let ref_set = payload.json::<schema::RefSet>();
+                ref_set.ok_or_else(|| anyhow!("ref set was not decoded"))
+            })
+            .and_then(|ref_set| {
+                let set = get_ref_set(&ref_set)?;
+
+                // Servers which ignore the `offset` parameter keep returning the first page, which
+                // must not be appended to the row again, so pagination stops right there.
+                if !is_requested_page(set, requested_offset) {
+                    return Ok(None);
+                }
+
+                let items = self.insert_tiles(row, anchor_id, set.items(), widgets)?;
+                self.rows[row].items.extend(items);
+                get_next_page(set)
+            });
+
+        let is_empty = widgets.get_children_of(anchor_id).is_empty();
+        match result {
+            // If none of the tiles on this page are shown, try the next page right away, unless
+            // too many pages have come up empty already.
+            Ok(Some(next_page)) if is_empty && self.rows[row].empty_pages < MAX_EMPTY_PAGES => {
+                self.rows[row].empty_pages += 1;
+                self.request_row(row, next_page);
+            }
+            Ok(_) if is_empty => self.collapse_row(row, widgets),
+            Ok(next_page) => {
+                self.rows[row].status = RowStatus::Loaded;
+                self.rows[row].next_page = next_page;
+
+                // If the cursor is parked on this row, it can finally select one of its tiles.
+                let (cur_row, cur_column) = self.selected_tile;
+                if cur_row == row && was_empty {
+                    self.select_tile(cur_row, cur_column, widgets);
+                }
+            }
+            Err(_) if !was_empty => {
+                // Failing to fetch an additional page is not fatal, but we stop paginating.
+                self.rows[row].status = RowStatus::Loaded;
+                self.rows[row].next_page = None;
+            }
+            Err(e) => {
+                self.rows[row].status = RowStatus::Failed;
+
+                let message = match e.downcast_ref::<FetchError>() {
+                    Some(e) if e.is_not_found() => ROW_NOT_FOUND_MESSAGE.to_owned(),
+                    _ => ROW_ERROR_MESSAGE.to_owned(),
+                };
+
+                // The message is attached to the row label, so that it moves along with the row
+                // if a row above it is collapsed.
+                let (max_width, _) = widgets.get(widgets.root()).bounds();
+                // The row may still be moving, so place the message where it comes to rest.
+                let (x, y) = widgets.get(anchor_id).target_origin();
+                let label = self.new_label(message, ERROR_POINT_SIZE, x, y, max_width);
+                let _label_id = widgets.insert(label, self.rows[row].label);
+            }
+        }
+    }
+
+    /// Hides a row which has no tiles to show, and moves the rows below it up to close the gap.
+    ///
+    /// If the cursor is parked on the row, it moves to the nearest row which is not skipped over.
+    fn collapse_row(&mut self, row: usize, widgets: &mut Widgets<WidgetKind>) {
+        self.rows[row].status = RowStatus::Empty;
+        self.rows[row].next_page = None;
+        widgets.get_mut(self.rows[row].label).set_hidden(true);
+
+        for below in &self.rows[row + 1..] {
+            widgets.translate(below.label, 0, -(ROW_HEIGHT as i32), SCROLL_TRANSITION);
+            widgets.translate(below.anchor, 0, -(ROW_HEIGHT as i32), SCROLL_TRANSITION);
+        }
+
+        widgets.get_mut(self.grid_root).properties_mut().bounds.1 -= ROW_HEIGHT;
+
+        // Explain why the menu is blank if kids mode filtered out every single row.
+        if self.kids_mode && self.rows.iter().all(Row::is_collapsed) {
+            let message = NO_KIDS_CONTENT_MESSAGE.to_owned();
+            widgets.get_mut(self.status_label).set_text(message);
+            widgets.get_mut(self.status_label).set_hidden(false);
+        }
+
+        let (cur_row, column) = self.selected_tile;
+        if cur_row == row {
+            let nearest = (row + 1..self.rows.len())
+                .find(|&i| !self.rows[i].is_skipped())
+                .or_else(|| (0..row).rev().find(|&i| !self.rows[i].is_skipped()));
+
+            if let Some(nearest) = nearest {
+                self.select_tile(nearest, column, widgets);
+            }
+        }
+    }
+
+    /// Returns the position of the given row on screen, counting only rows which are not
+    /// collapsed.
+    fn visual_index(&self, row: usize) -> usize {
+        let row = row.min(self.rows.len());
+        self.rows[..row]
+            .iter()
+            .filter(|r| !r.is_collapsed())
+            .count()
+    }
+}
+
+/// Shows or hides every descendant of the widget named `id`, but not the widget itself.
+fn set_descendants_hidden(widgets: &Widgets<WidgetKind>, id: WidgetId, hidden: bool) {
+    for &child_id in widgets.get_children_of(id) {
+        widgets.get_mut(child_id).set_hidden(hidden);
+        set_descendants_hidden(widgets, child_id, hidden);
+    }
+}
+
+/// Returns the location of the tile at the given column of the row attached to `anchor`.
+///
+/// The anchor may have been scrolled already, or may still be scrolling, so the tile is positioned
+/// relative to where the anchor comes to rest. Tiles inserted while the anchor is moving do not
+/// follow the rest of its animation.
+fn get_tile_origin(anchor: &WidgetKind, column: usize) -> (i32, i32) {
+    let (anchor_x, anchor_y) = anchor.target_origin();
+    let x = anchor_x + (column as u32 * (TILE_WIDTH + TILE_MARGIN)) as i32;
+    (x, anchor_y)
+}
+
+/// Returns a digest of the `home.json` document, which changes whenever its contents do.
+fn digest_home(home: &schema::Home) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&serde_json::to_vec(home).unwrap_or_default());
+    hasher.finish()
+}
+
+/// Returns the absolute difference between two indices.
+#[inline]
+fn distance(a: usize, b: usize) -> usize {
+    a.max(b) - a.min(b)
 }
 
 /// Computes the array index of the menu tile widget we want to select using the scroll offsets.
@@ -209,76 +1113,91 @@ impl State<WidgetKind> for Menu {
     fn initialize(&mut self, widgets: &mut Widgets<WidgetKind>) -> anyhow::Result<()> {
         let (max_width, _) = widgets.get(widgets.root()).bounds();
 
-        // This is the invisible anchor point to which the entire menu can be scrolled up/down.
-        self.grid_root = widgets.insert(WidgetKind::new_anchor(0, 0), widgets.root());
+        // Display a loading screen with placeholder rows right away. The menu grid is built once
+        // `home.json` has been downloaded, without blocking the main loop in the meantime.
+        widgets
+            .get_mut(widgets.root())
+            .set_color(self.theme().background);
+
+        let label = self.new_label(
+            LOADING_MESSAGE.to_owned(),
+            LABEL_POINT_SIZE,
+            RIGHT_MARGIN,
+            TOP_MARGIN,
+            max_width,
+        );
 
-        let url = HOME_JSON_URL.parse()?;
-        let home_menu = download_home_json(url, &self.fetcher)?;
-        let rows = get_menu_rows(&home_menu)?;
-        self.rows.reserve(rows.len());
+        let (_, label_height) = label.bounds();
+        self.skeleton = widgets.insert(WidgetKind::new_anchor(0, 0), widgets.root());
+        self.status_label = widgets.insert(label, widgets.root());
 
-        for (i, row) in rows.iter().enumerate() {
-            let (label_x, label_y, label_height) = {
-                let title = get_row_title(row, i)?;
+        let columns = max_width / (TILE_WIDTH + TILE_MARGIN) + 1;
+        for i in 0..SKELETON_ROWS {
+            let y = TOP_MARGIN + (i * ROW_HEIGHT + label_height + LABEL_PADDING) as i32;
+            for j in 0..columns {
+                let x = RIGHT_MARGIN + (j * (TILE_WIDTH + TILE_MARGIN)) as i32;
+                let _tile_id = widgets.insert(self.new_tile(x, y), self.skeleton);
+            }
+        }
 
-                let label = WidgetKind::new_label(
-                    title.to_owned(),
-                    LABEL_POINT_SIZE,
-                    RIGHT_MARGIN,
-                    TOP_MARGIN + (i as u32 * ROW_HEIGHT) as i32,
-                    max_width,
-                );
+        self.fetcher
+            .set_timeouts(self.home_url.clone(), HOME_TIMEOUTS);
 
-                // We affix labels to `grid_root` so that it can scroll up/down as the user presses
-                // `UP` and `DOWN`, but remains stationary when the user scrolls left/right.
-                let (x, y) = label.origin();
-                let (_, height) = label.bounds();
-                let _label_id = widgets.insert(label, self.grid_root);
+        Ok(())
+    }
 
-                (x, y, height)
-            };
+    fn on_resume(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        self.dispatch_completions(widgets);
 
-            match &row.set {
-                Set::Curated { items, .. } => {
-                    // This invisible anchor point is used to scroll the current row of tiles
-                    // left/right independently of all the other rows.
-                    let row_id =
-                        widgets.insert(WidgetKind::new_anchor(label_x, label_y), self.grid_root);
-
-                    // Mark that the current row hasn't been scrolled horizontally by the user yet.
-                    // This value comes in handy later in `select_tile()`.
-                    let scroll_offset = 0;
-                    self.rows.push((row_id, scroll_offset));
-
-                    // Create a row of tiles whose thumbnails are loaded in asynchronously.
-                    for (j, tile) in items.iter().enumerate() {
-                        let image_url = get_tile_image_url(&tile)?;
-
-                        let tile = WidgetKind::new_tile(
-                            RIGHT_MARGIN + (j as u32 * (TILE_WIDTH + TILE_MARGIN)) as i32,
-                            label_y + (label_height + LABEL_PADDING) as i32,
-                            image_url.clone(),
-                            self.fetcher.clone(),
-                        );
+        if self.unlocked.get() {
+            self.unlock_tiles(widgets);
+        }
+    }
 
-                        let _tile_id = widgets.insert(tile, row_id);
-                    }
+    fn update(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        self.dispatch_completions(widgets);
+
+        match self.home_status {
+            HomeStatus::Loading => self.load_home(widgets),
+            HomeStatus::Loaded => self.refresh_home(widgets),
+            HomeStatus::Failed => {}
+        }
+
+        if self.selected_tile != self.prioritized_tile {
+            self.reprioritize();
+        }
+
+        let (cur_row, cur_column) = self.selected_tile;
+
+        // Request the next page of the current row if the cursor is nearing its end.
+        if let Some(row) = self.rows.get_mut(cur_row) {
+            let num_tiles = widgets.get_children_of(row.anchor).len();
+            let near_end = cur_column + PAGINATION_DISTANCE >= num_tiles;
 
-                    // Increment the height of `grid_root` so that its dimensions include this row.
-                    widgets.get_mut(self.grid_root).properties_mut().bounds.1 += ROW_HEIGHT;
+            if let (RowStatus::Loaded, true) = (&row.status, near_end) {
+                if let Some(page) = row.next_page.take() {
+                    self.request_row(cur_row, page);
                 }
-                Set::Ref { .. } => {} // TODO: Need to implement lazy ref set loading.
             }
         }
 
-        // The menu grid is populated, so select the current tile.
-        let (row, column) = self.selected_tile;
-        self.select_tile(row, column, widgets);
+        // Request the ref sets of all rows which are close enough to the cursor.
+        let first = cur_row.saturating_sub(LAZY_LOAD_DISTANCE);
+        let last = (cur_row + LAZY_LOAD_DISTANCE + 1).min(self.rows.len());
 
-        Ok(())
+        for row in first..last {
+            if let RowStatus::Unloaded(ref page) = self.rows[row].status {
+                let page = page.clone();
+                self.request_row(row, page);
+            }
+        }
     }
 
-    fn handle_event(&mut self, event: &Event, widgets: &mut Widgets<WidgetKind>) -> Action {
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        widgets: &mut Widgets<WidgetKind>,
+    ) -> Action<WidgetKind> {
         match *event {
             Event::Quit { .. } => return Action::Quit,
             Event::KeyDown { keycode, .. } => match keycode {
@@ -286,6 +1205,11 @@ impl State<WidgetKind> for Menu {
                 Some(Keycode::Down) => self.move_down(widgets),
                 Some(Keycode::Left) => self.move_left(widgets),
                 Some(Keycode::Right) => self.move_right(widgets),
+                Some(Keycode::Return) if matches!(self.home_status, HomeStatus::Failed) => {
+                    self.retry_home(widgets)
+                }
+                Some(Keycode::Return) => return self.open_details(),
+                Some(Keycode::K) => self.toggle_kids_mode(widgets),
                 Some(Keycode::Escape) => return Action::Quit,
                 Some(Keycode::F11) => return Action::Fullscreen(Fullscreen::Toggle),
                 _ => {}
@@ -309,6 +1233,7 @@ pub enum WidgetKind {
     Label {
         text: String,
         point_size: u16,
+        background: Color,
         properties: Properties,
     },
     Tile {
@@ -348,6 +1273,7 @@ impl WidgetKind {
         WidgetKind::Label {
             text,
             point_size,
+            background: BACKGROUND_COLOR,
             properties: Properties {
                 origin: (x, y),
                 bounds: (max_width, approx_height),
@@ -356,18 +1282,72 @@ impl WidgetKind {
         }
     }
 
+    /// Creates a new bordered label which fits the given text, e.g. a content rating.
+    pub fn new_badge(text: String, point_size: u16, x: i32, y: i32) -> Self {
+        // Pad the text with spaces so that it does not touch the border, and approximate the
+        // width of the text generously so that it never wraps.
+        let text = format!(" {} ", text);
+        let approx_width = text.chars().count() as u32 * point_size as u32 * 3 / 4;
+        let mut badge = WidgetKind::new_label(text, point_size, x, y, approx_width);
+        badge.set_border(BADGE_BORDER_COLOR, BADGE_BORDER_WIDTH);
+        badge
+    }
+
     /// Creates a new image tile of a fixed size located at the given (X, Y) coordinate.
-    pub fn new_tile(x: i32, y: i32, image_url: Url, fetcher: Rc<Fetcher>) -> Self {
+    ///
+    /// The tile is blank until its thumbnail is set with [`WidgetKind::set_thumbnail()`].
+    pub fn new_tile(x: i32, y: i32) -> Self {
+        let mut tile = WidgetKind::new_image(x, y, TILE_WIDTH, TILE_HEIGHT);
+        tile.set_color(TILE_COLOR);
+        tile
+    }
+
+    /// Creates a new image of the given size located at the given (X, Y) coordinate.
+    ///
+    /// The image is blank until it is set with [`WidgetKind::set_thumbnail()`].
+    pub fn new_image(x: i32, y: i32, width: u32, height: u32) -> Self {
         WidgetKind::Tile {
-            image: Thumbnail::Pending(fetcher, image_url),
+            image: Thumbnail::Pending,
             properties: Properties {
                 origin: (x, y),
-                bounds: (TILE_WIDTH, TILE_HEIGHT),
-                color: TILE_COLOR,
+                bounds: (width, height),
                 ..Default::default()
             },
         }
     }
+
+    /// Replaces the text of a label widget and redraws it. Other widgets are left unchanged.
+    pub fn set_text(&mut self, new_text: String) {
+        if let WidgetKind::Label {
+            text, properties, ..
+        } = self
+        {
+            *text = new_text;
+            properties.invalidated = true;
+        }
+    }
+
+    /// Replaces the background color of a label widget and redraws it. Other widgets are left
+    /// unchanged.
+    pub fn set_background(&mut self, color: Color) {
+        if let WidgetKind::Label {
+            background,
+            properties,
+            ..
+        } = self
+        {
+            *background = color;
+            properties.invalidated = true;
+        }
+    }
+
+    /// Replaces the thumbnail of a tile widget and redraws it. Other widgets are left unchanged.
+    pub fn set_thumbnail(&mut self, thumbnail: Thumbnail) {
+        if let WidgetKind::Tile { image, properties } = self {
+            *image = thumbnail;
+            properties.invalidated = true;
+        }
+    }
 }
 
 impl Widget for WidgetKind {
@@ -389,18 +1369,6 @@ impl Widget for WidgetKind {
         }
     }
 
-    fn update(&mut self) {
-        if let WidgetKind::Tile { image, properties } = self {
-            let prev_state = image.is_ready();
-            let next_state = image.poll_ready().transpose().is_some();
-
-            // Redraw tile widgets if the thumbnail is done downloading.
-            if prev_state != next_state {
-                properties.invalidated = true;
-            }
-        }
-    }
-
     fn draw(&mut self, ctx: &mut Context, target: &mut Texture) -> anyhow::Result<()> {
         match self {
             WidgetKind::Root { properties } | WidgetKind::Anchor { properties } => {
@@ -414,32 +1382,29 @@ impl Widget for WidgetKind {
                 properties,
                 text,
                 point_size,
+                background,
             } => {
-                let text = ctx.textures.render_text(
-                    FONT_PATH,
-                    *point_size,
-                    FONT_STYLE,
-                    properties,
-                    &text,
-                )?;
-
-                let (width, height) = text.bounds;
-                let rect = Rect::new(0, 0, width, height);
+                let textures = &mut *ctx.textures;
+                let text =
+                    textures.layout_text(FONT_PATH, *point_size, FONT_STYLE, properties, &text)?;
+
+                let mut result = Ok(());
                 ctx.canvas.with_texture_canvas(target, |texture| {
-                    texture.set_draw_color(BACKGROUND_COLOR);
+                    texture.set_draw_color(*background);
                     texture.clear();
-                    texture.copy(&text.texture, None, rect).unwrap();
+                    result = textures.draw_text(texture, &text, 0, 0);
                 })?;
+                result?;
             }
             WidgetKind::Tile { properties, image } => {
                 let textures = &mut ctx.textures;
 
-                // If the thumbnail is ready but resulted in error, or if the file could not be
-                // loaded as a texture, just show a blank tile.
-                let thumbnail = image
-                    .poll_ready()
-                    .transpose()
-                    .and_then(|result| result.and_then(|path| textures.load_image(&path)).ok());
+                // If the thumbnail failed to download, or if the file could not be loaded as a
+                // texture, just show a blank tile.
+                let thumbnail = match image {
+                    Thumbnail::Ready(path) => textures.load_image(path.as_path()).ok(),
+                    Thumbnail::Pending | Thumbnail::Failed => None,
+                };
 
                 let (width, height) = properties.bounds;
                 let rect = Rect::new(0, 0, width, height);
@@ -465,50 +1430,55 @@ pub enum Thumbnail {
     /// Represents a downloaded thumbnail that is cached on disk.
     Ready(PathBuf),
     /// Represents a thumbnail that is currently being downloaded.
-    Pending(Rc<Fetcher>, Url),
+    Pending,
+    /// Represents a thumbnail that could not be downloaded.
+    Failed,
 }
 
-impl Thumbnail {
-    /// Attempts to return the path to the downloaded image file, if it is ready.
-    ///
-    /// This method does _not_ block if the file is not ready. If the download is still pending,
-    /// the current status can be polled again by repeatedly calling this method. If the file from
-    /// the requested URL already exists on disk, its path will be returned immediately.
-    ///
-    /// Returns `Err(_)` if the file at the target URL does not exist, an I/O error occurred, or
-    /// the background worker thread was terminated.
-    fn poll_ready(&mut self) -> anyhow::Result<Option<&PathBuf>> {
-        match *self {
-            Thumbnail::Ready(ref path) => Ok(Some(path)),
-            Thumbnail::Pending(ref fetcher, ref url) => match fetcher.poll_fetch(url.clone()) {
-                Poll::Pending => Ok(None),
-                Poll::Ready(result) => {
-                    let path = result?;
-                    *self = Thumbnail::Ready(path);
-                    self.poll_ready()
-                }
-            },
+pub(crate) fn get_ref_set_url(ref_id: &Uuid) -> anyhow::Result<Url> {
+    let url = format!("{}{}.json", REF_SET_URL, ref_id).parse()?;
+    Ok(url)
+}
+
+pub(crate) fn get_ref_set(set: &schema::RefSet) -> anyhow::Result<&Set> {
+    match set.data.values().next() {
+        Some(set @ Set::Curated { .. }) => Ok(set),
+        Some(Set::Ref { ref_id, .. }) => {
+            Err(anyhow!("ref set refers to another ref set {}", ref_id))
         }
+        None => Err(anyhow!("ref set does not contain any sets")),
     }
+}
 
-    /// Returns `true` if the thumbnail is cached on disk, ready to display.
-    #[inline]
-    fn is_ready(&self) -> bool {
-        match *self {
-            Thumbnail::Ready(_) => true,
-            _ => false,
+pub(crate) fn get_next_page(set: &Set) -> anyhow::Result<Option<Page>> {
+    let meta = match set.meta() {
+        Some(meta) => meta,
+        None => return Ok(None),
+    };
+
+    match meta.next_offset() {
+        Some(offset) => {
+            let set_url = get_ref_set_url(set.id())?;
+            Ok(Some(Page {
+                url: meta.page_url(&set_url, offset),
+                offset: Some(offset),
+            }))
         }
+        None => Ok(None),
     }
 }
 
-fn download_home_json(url: Url, fetcher: &Fetcher) -> anyhow::Result<schema::Home> {
-    let path = fetcher.fetch(url)?;
-    let json = std::fs::read_to_string(path)?;
-    let menu = serde_json::from_str(&json)?;
-    Ok(menu)
+/// Returns `true` if `set` is the page beginning at item `offset`, or if no specific page was
+/// requested.
+pub(crate) fn is_requested_page(set: &Set, offset: Option<u32>) -> bool {
+    match (set.meta(), offset) {
+        (_, None) => true,
+        (Some(meta), Some(offset)) => meta.offset == offset,
+        (None, Some(_)) => false,
+    }
 }
 
-fn get_menu_rows(menu: &schema::Home) -> anyhow::Result<&[schema::Container]> {
+pub(crate) fn get_menu_rows(menu: &schema::Home) -> anyhow::Result<&[schema::Container]> {
     menu.data
         .get("StandardCollection")
         .ok_or(anyhow!("key `StandardCollection` does not exist"))?
@@ -525,7 +1495,14 @@ fn get_row_title(row: &schema::Container, row_idx: usize) -> anyhow::Result<&str
         .map(|text| text.content.as_str())
 }
 
-fn get_tile_image_url(tile: &schema::Collection) -> anyhow::Result<&Url> {
+/// Returns `true` if the content is marked as suitable for kids mode.
+fn is_kids_available(item: &schema::Collection) -> bool {
+    item.current_availability()
+        .and_then(|availability| availability.kids_mode)
+        .unwrap_or(false)
+}
+
+pub(crate) fn get_tile_image_url(tile: &schema::Collection) -> anyhow::Result<&Url> {
     let tile_name = tile
         .text()
         .title
@@ -553,6 +1530,12 @@ fn get_tile_image_url(tile: &schema::Collection) -> anyhow::Result<&Url> {
         })
 }
 
+/// Returns the URLs of the images displayed on the details screen of the given tile.
+pub(crate) fn get_details_image_urls(tile: &schema::Collection) -> Vec<Url> {
+    let content = Content::from_collection(tile);
+    content.image_urls().cloned().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use schema::Home;
@@ -560,6 +1543,10 @@ mod tests {
     use super::*;
 
     const HOME_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/home.json"));
+    const REF_SET_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/f506622c-4f75-4f87-bafe-3e08a4433914.json"
+    ));
 
     #[test]
     fn gets_menu_rows() {
@@ -593,6 +1580,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filters_kids_content() {
+        let mut json: serde_json::Value = serde_json::from_str(HOME_JSON).unwrap();
+        let items = "/data/StandardCollection/containers/0/set/items";
+        json.pointer_mut(&format!("{}/1/currentAvailability/kidsMode", items))
+            .map(|kids_mode| *kids_mode = true.into())
+            .expect("item must have an availability");
+
+        let h: Home = serde_json::from_value(json).expect("failed to deserialize `home.json`");
+        let rows = get_menu_rows(&h).expect("failed to get home menu rows");
+
+        // Titles explicitly marked as unavailable in kids mode, and collections without any
+        // availability at all, are filtered out.
+        let items = rows[0].set.items();
+        assert!(!is_kids_available(&items[0]));
+        assert!(is_kids_available(&items[1]));
+        assert!(!is_kids_available(&rows[1].set.items()[0]));
+    }
+
+    #[test]
+    fn digests_home() {
+        let home: Home = serde_json::from_str(HOME_JSON).expect("failed to deserialize");
+        let same: Home = serde_json::from_str(HOME_JSON).expect("failed to deserialize");
+        assert_eq!(digest_home(&home), digest_home(&same));
+
+        let mut json: serde_json::Value = serde_json::from_str(HOME_JSON).unwrap();
+        json.pointer_mut("/data/StandardCollection/containers/0/set/items/0/contentId")
+            .map(|content_id| *content_id = Uuid::nil().to_string().into())
+            .expect("item must have a content ID");
+        let changed: Home = serde_json::from_value(json).expect("failed to deserialize");
+        assert_ne!(digest_home(&home), digest_home(&changed));
+    }
+
+    #[test]
+    fn gets_ref_set_url() {
+        let ref_id = "f506622c-4f75-4f87-bafe-3e08a4433914".parse().unwrap();
+        let url = get_ref_set_url(&ref_id).expect("failed to build ref set URL");
+        assert_eq!(
+            url.as_str(),
+            "https://cd-static.bamgrid.com/dp-117731241344/sets/f506622c-4f75-4f87-bafe-3e08a4433914.json"
+        );
+    }
+
+    #[test]
+    fn gets_ref_set_items() {
+        let s: schema::RefSet = serde_json::from_str(REF_SET_JSON).expect("failed to deserialize");
+        let set = get_ref_set(&s).expect("failed to get ref set");
+        assert_eq!(set.items().len(), 15);
+
+        for item in set.items() {
+            get_tile_image_url(item).expect("image not found");
+        }
+    }
+
+    #[test]
+    fn gets_next_page_url() {
+        let s: schema::RefSet = serde_json::from_str(REF_SET_JSON).expect("failed to deserialize");
+        let set = get_ref_set(&s).expect("failed to get ref set");
+        let page = get_next_page(set).expect("failed to build page URL");
+        let page = page.expect("ref set has more than one page");
+        assert_eq!(
+            page.url.as_str(),
+            "https://cd-static.bamgrid.com/dp-117731241344/sets/f506622c-4f75-4f87-bafe-3e08a4433914.json?offset=15&page_size=15"
+        );
+        assert_eq!(page.offset, Some(15));
+    }
+
+    #[test]
+    fn ignores_repeated_pages() {
+        let s: schema::RefSet = serde_json::from_str(REF_SET_JSON).expect("failed to deserialize");
+        let set = get_ref_set(&s).expect("failed to get ref set");
+        assert!(is_requested_page(set, None));
+
+        // Simulate a server which ignores `?offset=`, so every request returns the first page.
+        let mut items = Vec::new();
+        let mut next = Some(Page {
+            url: get_ref_set_url(set.id()).unwrap(),
+            offset: None,
+        });
+
+        while let Some(page) = next.take() {
+            let fetched = get_ref_set(&s).expect("failed to get ref set");
+            if is_requested_page(fetched, page.offset) {
+                items.extend(fetched.items().iter().map(|item| item.content_id()));
+                next = get_next_page(fetched).expect("failed to build page URL");
+            }
+        }
+
+        let unique: std::collections::HashSet<_> = items.iter().collect();
+        assert_eq!(items.len(), 15);
+        assert_eq!(unique.len(), items.len());
+    }
+
+    #[test]
+    fn positions_tiles_where_scrolling_anchor_comes_to_rest() {
+        let tile_width = (TILE_WIDTH + TILE_MARGIN) as i32;
+        let mut anchor = WidgetKind::new_anchor(0, 100);
+        anchor.animate_origin(-tile_width, 50, SCROLL_TRANSITION);
+        assert_eq!(anchor.origin(), (0, 100));
+
+        assert_eq!(get_tile_origin(&anchor, 0), (-tile_width, 50));
+        assert_eq!(get_tile_origin(&anchor, 3), (2 * tile_width, 50));
+    }
+
     #[test]
     fn computes_adjacent_tile_index() {
         let requested_column = 4;
@@ -0,0 +1,283 @@
+//! Details screen for a single piece of content, opened by selecting a tile in the menu.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use url::Url;
+use uuid::Uuid;
+
+use super::{
+    Thumbnail, WidgetKind, BACKGROUND_COLOR, ERROR_POINT_SIZE, LABEL_PADDING, LABEL_POINT_SIZE,
+    RIGHT_MARGIN, TOP_MARGIN,
+};
+use crate::app::{Action, Fullscreen, State, Widget, WidgetId, Widgets};
+use crate::fetcher::{Completion, Fetcher, Payload, Priority};
+use crate::schema::{self, MediaRights};
+
+/// Background images to display, in order of preference.
+const BACKGROUND_IMAGE_NAMES: &[&str] = &["background_details", "background", "hero_collection"];
+const BACKGROUND_ASPECT_RATIO: &str = "1.78";
+
+const TITLE_IMAGE_NAME: &str = "title_treatment";
+const TITLE_ASPECT_RATIO: &str = "1.78";
+const TITLE_WIDTH: u32 = 640;
+const TITLE_HEIGHT: u32 = 360;
+
+const BADGE_POINT_SIZE: u16 = 28;
+const BADGE_MARGIN: u32 = 16;
+const ORIGINAL_BADGE: &str = "Original";
+
+const DOWNLOAD_BLOCKED_MESSAGE: &str = "Not available for download";
+const DOWNLOAD_ALLOWED_MESSAGE: &str = "Available for download";
+const PCON_BLOCKED_MESSAGE: &str = "Restricted by parental controls";
+
+/// Metadata of a menu tile, retained so that its details can be displayed once it is selected.
+#[derive(Clone, Debug)]
+pub(super) struct Content {
+    title: String,
+    background: Option<Url>,
+    title_treatment: Option<Url>,
+    rating: Option<String>,
+    release_year: Option<u16>,
+    is_original: bool,
+    media_rights: Option<MediaRights>,
+    series_id: Option<Uuid>,
+}
+
+impl Content {
+    /// Extracts the details to be displayed from a collection in the menu.
+    pub fn from_collection(item: &schema::Collection) -> Self {
+        let title = item
+            .text()
+            .title
+            .get(schema::TitleKind::Full)
+            .map(|text| text.content.clone())
+            .unwrap_or_default();
+
+        let background = BACKGROUND_IMAGE_NAMES
+            .iter()
+            .find_map(|name| get_image_url(item, name, BACKGROUND_ASPECT_RATIO));
+
+        Content {
+            title,
+            background,
+            title_treatment: get_image_url(item, TITLE_IMAGE_NAME, TITLE_ASPECT_RATIO),
+            rating: item.ratings().first().map(|rating| rating.value.clone()),
+            release_year: item.release_year(),
+            is_original: item.is_original(),
+            media_rights: item.media_rights().cloned(),
+            series_id: item.series_id().copied(),
+        }
+    }
+
+    /// Returns the URLs of every image displayed on the details screen.
+    pub fn image_urls(&self) -> impl Iterator<Item = &Url> {
+        self.background.iter().chain(self.title_treatment.iter())
+    }
+
+    /// Returns the text of every badge to display beneath the title, in order.
+    fn badges(&self) -> Vec<String> {
+        let mut badges = Vec::new();
+        badges.extend(self.rating.clone());
+        badges.extend(self.release_year.map(|year| year.to_string()));
+        if self.is_original {
+            badges.push(ORIGINAL_BADGE.to_owned());
+        }
+        badges
+    }
+
+    /// Returns the lines of text to display beneath the badges, in order.
+    fn info_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if let Some(ref rights) = self.media_rights {
+            lines.push(if rights.download_blocked {
+                DOWNLOAD_BLOCKED_MESSAGE.to_owned()
+            } else {
+                DOWNLOAD_ALLOWED_MESSAGE.to_owned()
+            });
+
+            if rights.pcon_blocked {
+                lines.push(PCON_BLOCKED_MESSAGE.to_owned());
+            }
+        }
+
+        if let Some(ref series_id) = self.series_id {
+            lines.push(format!("Series ID: {}", series_id));
+        }
+
+        lines
+    }
+}
+
+/// Contains the state for the details screen of a single piece of content.
+///
+/// The details screen covers the entire menu, which is restored with its cursor intact once the
+/// user goes back.
+#[derive(Debug)]
+pub(super) struct Details {
+    fetcher: Rc<Fetcher>,
+    content: Content,
+    /// Images which are still being downloaded, along with the widgets which display them.
+    pending: Vec<(Url, WidgetId)>,
+    /// Completion events are handed back to the paused menu through this buffer, so that it can
+    /// dispatch the downloads it is waiting for once it resumes.
+    deferred: Rc<RefCell<Vec<Completion>>>,
+}
+
+impl Details {
+    /// Creates a new details screen for the given content.
+    ///
+    /// Every completion event received while the details screen is active is pushed onto
+    /// `deferred`, including those of its own images.
+    #[inline]
+    pub fn new(
+        fetcher: Rc<Fetcher>,
+        content: Content,
+        deferred: Rc<RefCell<Vec<Completion>>>,
+    ) -> Self {
+        Details {
+            fetcher,
+            content,
+            pending: Vec::new(),
+            deferred,
+        }
+    }
+}
+
+impl State<WidgetKind> for Details {
+    fn initialize(&mut self, widgets: &mut Widgets<WidgetKind>) -> anyhow::Result<()> {
+        let (width, height) = widgets.get(widgets.root()).bounds();
+
+        // The background covers the entire window, hiding the menu below.
+        let mut background = WidgetKind::new_image(0, 0, width, height);
+        background.set_color(BACKGROUND_COLOR);
+        let background_id = widgets.insert(background, widgets.root());
+        if let Some(url) = self.content.background.clone() {
+            self.pending.push((url, background_id));
+        }
+
+        // Prefer the stylized title treatment image, falling back to plain text.
+        let mut y = TOP_MARGIN;
+        match self.content.title_treatment.clone() {
+            Some(url) => {
+                let mut title = WidgetKind::new_image(RIGHT_MARGIN, y, TITLE_WIDTH, TITLE_HEIGHT);
+                title.set_color(BACKGROUND_COLOR);
+                let title_id = widgets.insert(title, widgets.root());
+                self.pending.push((url, title_id));
+                y += TITLE_HEIGHT as i32;
+            }
+            None => {
+                let title = self.content.title.clone();
+                let label = WidgetKind::new_label(title, LABEL_POINT_SIZE, RIGHT_MARGIN, y, width);
+                let (_, label_height) = label.bounds();
+                let _label_id = widgets.insert(label, widgets.root());
+                y += label_height as i32;
+            }
+        }
+
+        y += LABEL_PADDING as i32;
+
+        let mut x = RIGHT_MARGIN;
+        let mut badge_height = 0;
+        for text in self.content.badges() {
+            let badge = WidgetKind::new_badge(text, BADGE_POINT_SIZE, x, y);
+            let (badge_width, height) = badge.bounds();
+            let _badge_id = widgets.insert(badge, widgets.root());
+            x += (badge_width + BADGE_MARGIN) as i32;
+            badge_height = height;
+        }
+
+        y += (badge_height + LABEL_PADDING) as i32;
+
+        for line in self.content.info_lines() {
+            let label = WidgetKind::new_label(line, ERROR_POINT_SIZE, RIGHT_MARGIN, y, width);
+            let (_, label_height) = label.bounds();
+            let _label_id = widgets.insert(label, widgets.root());
+            y += label_height as i32;
+        }
+
+        // These images are displayed right away, so they are downloaded before anything else.
+        for (url, _) in &self.pending {
+            self.fetcher.forget_failure(url.clone());
+            self.fetcher.subscribe(url.clone(), Priority::HIGHEST);
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, widgets: &mut Widgets<WidgetKind>) {
+        let mut deferred = self.deferred.borrow_mut();
+        for completion in self.fetcher.completions() {
+            // The menu may be waiting for the same file, so the event is handed back regardless.
+            self.pending.retain(|(url, id)| {
+                if *url != completion.url {
+                    return true;
+                }
+
+                let thumbnail = match completion.result {
+                    Ok(Payload::File(ref path)) => Thumbnail::Ready(path.clone()),
+                    Ok(Payload::Json(_)) | Err(_) => Thumbnail::Failed,
+                };
+
+                widgets.get_mut(*id).set_thumbnail(thumbnail);
+                false
+            });
+
+            deferred.push(completion);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        _widgets: &mut Widgets<WidgetKind>,
+    ) -> Action<WidgetKind> {
+        match *event {
+            Event::Quit { .. } => Action::Quit,
+            Event::KeyDown { keycode, .. } => match keycode {
+                Some(Keycode::Escape) | Some(Keycode::Backspace) => Action::Pop,
+                Some(Keycode::F11) => Action::Fullscreen(Fullscreen::Toggle),
+                _ => Action::Continue,
+            },
+            _ => Action::Continue,
+        }
+    }
+}
+
+/// Returns the URL of the image with the given name and aspect ratio, if it exists.
+fn get_image_url(item: &schema::Collection, name: &str, aspect_ratio: &str) -> Option<Url> {
+    item.images()
+        .get(name)
+        .and_then(|image| image.get(aspect_ratio))
+        .map(|image| image.url.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOME_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/home.json"));
+
+    #[test]
+    fn extracts_content_details() {
+        let home: schema::Home = serde_json::from_str(HOME_JSON).expect("failed to deserialize");
+        let rows = crate::menu::get_menu_rows(&home).expect("failed to get home menu rows");
+        let content = Content::from_collection(&rows[0].set.items()[0]);
+
+        assert_eq!(content.title, "The Right Stuff");
+        assert!(content.background.is_some());
+        assert!(content.title_treatment.is_some());
+        assert_eq!(content.image_urls().count(), 2);
+        assert_eq!(content.badges(), ["TV-14", "2020", ORIGINAL_BADGE]);
+        assert_eq!(
+            content.info_lines(),
+            [
+                DOWNLOAD_BLOCKED_MESSAGE.to_owned(),
+                "Series ID: 5ce23061-3c4a-40d8-9d08-179001be5a4f".to_owned(),
+            ]
+        );
+    }
+}
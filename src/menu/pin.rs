@@ -0,0 +1,154 @@
+//! Modal prompt for the parental control PIN, which reveals restricted tiles once entered.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+
+use super::{WidgetKind, BACKGROUND_COLOR, ERROR_POINT_SIZE, LABEL_PADDING, LABEL_POINT_SIZE};
+use crate::app::{Action, Fullscreen, State, Widget, WidgetId, Widgets};
+use crate::parental::ParentalControls;
+
+const PROMPT_MESSAGE: &str = "Enter the PIN to show restricted titles.";
+const INCORRECT_PIN_MESSAGE: &str = "Incorrect PIN, please try again.";
+
+const PANEL_WIDTH: u32 = 960;
+const PANEL_HEIGHT: u32 = 320;
+const PANEL_BORDER_COLOR: Color = Color::WHITE;
+const PANEL_BORDER_WIDTH: u8 = 4;
+const PANEL_PADDING: u32 = 40;
+
+/// The entered PIN is displayed as at least this many placeholders.
+const MIN_MASK_LENGTH: usize = 4;
+const MAX_PIN_LENGTH: usize = 8;
+
+/// Contains the state for the PIN prompt, which is displayed on top of the menu.
+///
+/// Once the correct PIN has been entered, `unlocked` is set and the prompt closes itself, so that
+/// the menu can reveal its restricted tiles when it resumes.
+#[derive(Debug)]
+pub(super) struct PinPrompt {
+    controls: Rc<ParentalControls>,
+    unlocked: Rc<Cell<bool>>,
+    entry: String,
+    message_label: WidgetId,
+    entry_label: WidgetId,
+}
+
+impl PinPrompt {
+    /// Creates a new PIN prompt which checks the PIN against the given parental controls.
+    #[inline]
+    pub fn new(controls: Rc<ParentalControls>, unlocked: Rc<Cell<bool>>) -> Self {
+        PinPrompt {
+            controls,
+            unlocked,
+            entry: String::new(),
+            message_label: WidgetId::root(),
+            entry_label: WidgetId::root(),
+        }
+    }
+
+    /// Redraws the entry label with one asterisk per entered digit.
+    fn update_entry(&self, widgets: &mut Widgets<WidgetKind>) {
+        let mask = mask_pin(self.entry.len());
+        widgets.get_mut(self.entry_label).set_text(mask);
+    }
+
+    /// Checks the entered PIN, closing the prompt if it is correct.
+    fn submit(&mut self, widgets: &mut Widgets<WidgetKind>) -> Action<WidgetKind> {
+        if self.controls.check_pin(&self.entry) {
+            self.unlocked.set(true);
+            return Action::Pop;
+        }
+
+        self.entry.clear();
+        self.update_entry(widgets);
+        let message = INCORRECT_PIN_MESSAGE.to_owned();
+        widgets.get_mut(self.message_label).set_text(message);
+        Action::Continue
+    }
+}
+
+impl State<WidgetKind> for PinPrompt {
+    fn initialize(&mut self, widgets: &mut Widgets<WidgetKind>) -> anyhow::Result<()> {
+        let (width, height) = widgets.get(widgets.root()).bounds();
+        let x = (width.saturating_sub(PANEL_WIDTH) / 2) as i32;
+        let y = (height.saturating_sub(PANEL_HEIGHT) / 2) as i32;
+
+        let mut panel = WidgetKind::new_image(x, y, PANEL_WIDTH, PANEL_HEIGHT);
+        panel
+            .set_color(BACKGROUND_COLOR)
+            .set_border(PANEL_BORDER_COLOR, PANEL_BORDER_WIDTH);
+        let panel_id = widgets.insert(panel, widgets.root());
+
+        let label_x = x + PANEL_PADDING as i32;
+        let label_y = y + PANEL_PADDING as i32;
+        let label_width = PANEL_WIDTH - PANEL_PADDING * 2;
+
+        let message = PROMPT_MESSAGE.to_owned();
+        let label = WidgetKind::new_label(message, ERROR_POINT_SIZE, label_x, label_y, label_width);
+        let (_, label_height) = label.bounds();
+        self.message_label = widgets.insert(label, panel_id);
+
+        let entry_y = label_y + (label_height + LABEL_PADDING) as i32;
+        let mask = mask_pin(0);
+        let entry = WidgetKind::new_label(mask, LABEL_POINT_SIZE, label_x, entry_y, label_width);
+        self.entry_label = widgets.insert(entry, panel_id);
+
+        Ok(())
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        widgets: &mut Widgets<WidgetKind>,
+    ) -> Action<WidgetKind> {
+        match *event {
+            Event::Quit { .. } => Action::Quit,
+            Event::TextInput { ref text, .. } => {
+                let digits = text.chars().filter(char::is_ascii_digit);
+                let room = MAX_PIN_LENGTH.saturating_sub(self.entry.len());
+                self.entry.extend(digits.take(room));
+                self.update_entry(widgets);
+                Action::Continue
+            }
+            Event::KeyDown { keycode, .. } => match keycode {
+                Some(Keycode::Return) | Some(Keycode::KpEnter) => self.submit(widgets),
+                Some(Keycode::Backspace) => {
+                    self.entry.pop();
+                    self.update_entry(widgets);
+                    Action::Continue
+                }
+                Some(Keycode::Escape) => Action::Pop,
+                Some(Keycode::F11) => Action::Fullscreen(Fullscreen::Toggle),
+                _ => Action::Continue,
+            },
+            _ => Action::Continue,
+        }
+    }
+}
+
+/// Returns an asterisk for each of the `len` entered digits, padded with underscores.
+fn mask_pin(len: usize) -> String {
+    (0..len.max(MIN_MASK_LENGTH))
+        .map(|i| if i < len { '*' } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_entered_pin() {
+        assert_eq!(mask_pin(0), "____");
+        assert_eq!(mask_pin(3), "***_");
+        assert_eq!(mask_pin(6), "******");
+    }
+}
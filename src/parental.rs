@@ -0,0 +1,204 @@
+//! Parental controls which restrict content rated above a maximum rating.
+//!
+//! Ratings are compared using per-system ordering tables loaded from JSON, so that regional
+//! rating systems can be supported without any code changes. The default tables are located in
+//! `assets/ratings.json`.
+
+use std::path::Path;
+
+use anyhow::Context;
+use fnv::FnvHashMap as HashMap;
+use serde::Deserialize;
+
+use crate::schema::Rating;
+
+/// The rating systems built into the application, e.g. `TVPG` and `MPAA`.
+const DEFAULT_RATING_SYSTEMS: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/ratings.json"));
+
+/// Ordering tables for a set of rating systems.
+///
+/// Each rating system maps to a list of its ratings, ordered from least to most restrictive.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RatingSystems(HashMap<String, Vec<String>>);
+
+impl RatingSystems {
+    /// Parses rating system ordering tables from a JSON object, e.g.
+    /// `{ "MPAA": ["G", "PG", "PG-13", "R", "NC-17"] }`.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(json).context("invalid rating systems")
+    }
+
+    /// Reads rating system ordering tables from the JSON file located at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read rating systems from {}", path.display()))?;
+        RatingSystems::from_json(&json)
+    }
+
+    /// Returns the position of `value` within the ordering of the rating `system`, where higher
+    /// positions are more restrictive.
+    ///
+    /// Returns `None` if either the rating system or the rating is unknown.
+    pub fn rank(&self, system: &str, value: &str) -> Option<usize> {
+        self.0.get(system)?.iter().position(|v| v == value)
+    }
+}
+
+impl Default for RatingSystems {
+    fn default() -> Self {
+        RatingSystems::from_json(DEFAULT_RATING_SYSTEMS).expect("built-in rating systems are valid")
+    }
+}
+
+/// A parental control profile, containing the maximum allowed rating for each rating system.
+///
+/// Content is allowed unless one of its ratings exceeds the limit of its rating system. Ratings
+/// which cannot be compared against the limit, e.g. because the rating is not part of the
+/// ordering table, are treated as exceeding it. Rating systems without a limit are unrestricted.
+///
+/// Content without any ratings is always allowed, since collections and other containers of
+/// rated content are never rated themselves.
+#[derive(Clone, Debug, Default)]
+pub struct ParentalControls {
+    systems: RatingSystems,
+    limits: HashMap<String, String>,
+    pin: Option<String>,
+}
+
+impl ParentalControls {
+    /// Creates a new profile using the given rating systems, without any limits.
+    #[inline]
+    pub fn new(systems: RatingSystems) -> Self {
+        ParentalControls {
+            systems,
+            limits: HashMap::default(),
+            pin: None,
+        }
+    }
+
+    /// Restricts content rated above `max_rating` in the given rating `system`.
+    #[inline]
+    pub fn with_limit<S, R>(mut self, system: S, max_rating: R) -> Self
+    where
+        S: Into<String>,
+        R: Into<String>,
+    {
+        self.limits.insert(system.into(), max_rating.into());
+        self
+    }
+
+    /// Allows restricted content to be revealed by entering the given PIN.
+    ///
+    /// This setting is not enabled by default, so restricted content cannot be revealed at all.
+    #[inline]
+    pub fn with_pin<P: Into<String>>(mut self, pin: P) -> Self {
+        self.pin = Some(pin.into());
+        self
+    }
+
+    /// Returns `true` if no limits have been set.
+    #[inline]
+    pub fn is_unrestricted(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Returns `true` if content with the given ratings may be shown.
+    ///
+    /// Unrated content is allowed, even if limits have been set.
+    pub fn allows(&self, ratings: &[Rating]) -> bool {
+        ratings.iter().all(|rating| {
+            let limit = match self.limits.get(&rating.system) {
+                Some(limit) => limit,
+                None => return true,
+            };
+
+            let rank = self.systems.rank(&rating.system, &rating.value);
+            let max_rank = self.systems.rank(&rating.system, limit);
+            match (rank, max_rank) {
+                (Some(rank), Some(max_rank)) => rank <= max_rank,
+                _ => false,
+            }
+        })
+    }
+
+    /// Returns `true` if restricted content can be revealed by entering a PIN.
+    #[inline]
+    pub fn has_pin(&self) -> bool {
+        self.pin.is_some()
+    }
+
+    /// Returns `true` if `pin` matches the PIN of this profile.
+    #[inline]
+    pub fn check_pin(&self, pin: &str) -> bool {
+        self.pin.as_deref() == Some(pin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(system: &str, value: &str) -> Rating {
+        Rating {
+            system: system.to_owned(),
+            value: value.to_owned(),
+            advisories: Vec::new(),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn restricts_ratings_above_limit() {
+        let controls = ParentalControls::default()
+            .with_limit("TVPG", "TV-PG")
+            .with_limit("MPAA", "PG");
+
+        assert!(controls.allows(&[rating("TVPG", "TV-Y7")]));
+        assert!(controls.allows(&[rating("TVPG", "TV-PG")]));
+        assert!(!controls.allows(&[rating("TVPG", "TV-14")]));
+        assert!(controls.allows(&[rating("MPAA", "G")]));
+        assert!(!controls.allows(&[rating("MPAA", "PG-13")]));
+
+        // Unknown ratings cannot be compared, so they are restricted.
+        assert!(!controls.allows(&[rating("MPAA", "Unrated")]));
+        // Rating systems without a limit are unrestricted.
+        assert!(controls.allows(&[rating("BBFC", "18")]));
+    }
+
+    #[test]
+    fn allows_unrated_content() {
+        // Even the strictest limits allow content without ratings.
+        let controls = ParentalControls::default()
+            .with_limit("TVPG", "TV-Y")
+            .with_limit("MPAA", "G");
+        assert!(!controls.is_unrestricted());
+        assert!(controls.allows(&[]));
+        assert!(!controls.allows(&[rating("TVPG", "TV-Y7")]));
+    }
+
+    #[test]
+    fn loads_custom_rating_systems() {
+        let systems = RatingSystems::from_json(r#"{ "BBFC": ["U", "PG", "12A", "15", "18"] }"#)
+            .expect("failed to parse rating systems");
+        assert_eq!(systems.rank("BBFC", "15"), Some(3));
+        assert_eq!(systems.rank("TVPG", "TV-14"), None);
+
+        let controls = ParentalControls::new(systems).with_limit("BBFC", "12A");
+        assert!(controls.allows(&[rating("BBFC", "PG")]));
+        assert!(!controls.allows(&[rating("BBFC", "15")]));
+    }
+
+    #[test]
+    fn checks_pin() {
+        let controls = ParentalControls::default();
+        assert!(!controls.has_pin());
+        assert!(!controls.check_pin(""));
+
+        let controls = controls.with_pin("1234");
+        assert!(controls.has_pin());
+        assert!(controls.check_pin("1234"));
+        assert!(!controls.check_pin("4321"));
+    }
+}
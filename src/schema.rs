@@ -1,5 +1,6 @@
 //! Types for deserializing DSS API responses.
 
+pub use self::content::{Availability, MediaRights, Rating, Release, Tag};
 pub use self::image::{ImageContent, ImageTile};
 pub use self::text::{Language, Text, TextContent, TitleKind, Titles};
 
@@ -8,9 +9,13 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 use uuid::Uuid;
 
+mod content;
 mod image;
 mod text;
 
+/// Kind of the tag which marks Disney+ Originals.
+const ORIGINAL_TAG: &str = "disneyPlusOriginal";
+
 /// An API response containing home menu data.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Home {
@@ -30,6 +35,15 @@ pub struct Collection {
     /// Indicates the collection kind and any custom fields.
     #[serde(flatten)]
     inner: CollectionInner,
+    /// Unique ID of the content, if this is a series or video collection.
+    #[serde(default)]
+    content_id: Option<Uuid>,
+    /// Short URL-safe ID of the series, if this is a series collection.
+    #[serde(default)]
+    encoded_series_id: Option<String>,
+    /// Unique ID of the localized text experience, if any.
+    #[serde(default)]
+    text_experience_id: Option<Uuid>,
     /// Image tiles to be displayed, keyed by name.
     #[serde(default)]
     image: HashMap<String, ImageTile>,
@@ -38,6 +52,21 @@ pub struct Collection {
     /// Miniature video art for the collection, if any.
     #[serde(default)]
     video_art: Vec<VideoArt>,
+    /// Content ratings, e.g. `TV-14`.
+    #[serde(default)]
+    ratings: Vec<Rating>,
+    /// Known releases of the content.
+    #[serde(default)]
+    releases: Vec<Release>,
+    /// Tags attached to the content.
+    #[serde(default)]
+    tags: Vec<Tag>,
+    /// Restrictions on how the content may be consumed, if any.
+    #[serde(default)]
+    media_rights: Option<MediaRights>,
+    /// Where and to whom the content is currently available, if known.
+    #[serde(default)]
+    current_availability: Option<Availability>,
 }
 
 impl Collection {
@@ -75,6 +104,75 @@ impl Collection {
     pub fn text(&self) -> &Text {
         &self.text
     }
+
+    /// Returns the unique ID of the content, if this is a series or video collection.
+    #[inline]
+    pub fn content_id(&self) -> Option<&Uuid> {
+        self.content_id.as_ref()
+    }
+
+    /// Returns the short URL-safe ID of the series, if this is a series collection.
+    #[inline]
+    pub fn encoded_series_id(&self) -> Option<&str> {
+        self.encoded_series_id.as_deref()
+    }
+
+    /// Returns the unique ID of the localized text experience, if any.
+    #[inline]
+    pub fn text_experience_id(&self) -> Option<&Uuid> {
+        self.text_experience_id.as_ref()
+    }
+
+    /// Returns the unique ID of the series, if this is a series collection.
+    #[inline]
+    pub fn series_id(&self) -> Option<&Uuid> {
+        match self.inner {
+            CollectionInner::DmcSeries { ref series_id } => Some(series_id),
+            _ => None,
+        }
+    }
+
+    /// Returns the content ratings of the collection, if any.
+    #[inline]
+    pub fn ratings(&self) -> &[Rating] {
+        &self.ratings
+    }
+
+    /// Returns the known releases of the collection, if any.
+    #[inline]
+    pub fn releases(&self) -> &[Release] {
+        &self.releases
+    }
+
+    /// Returns the year in which the collection was first released, if known.
+    pub fn release_year(&self) -> Option<u16> {
+        self.releases.iter().filter_map(|r| r.release_year).min()
+    }
+
+    /// Returns the tags attached to the collection, if any.
+    #[inline]
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    /// Returns `true` if the collection is a Disney+ Original.
+    pub fn is_original(&self) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| tag.kind == ORIGINAL_TAG && tag.value.as_deref() == Some("true"))
+    }
+
+    /// Returns the restrictions on how the content may be consumed, if any.
+    #[inline]
+    pub fn media_rights(&self) -> Option<&MediaRights> {
+        self.media_rights.as_ref()
+    }
+
+    /// Returns where and to whom the content is currently available, if known.
+    #[inline]
+    pub fn current_availability(&self) -> Option<&Availability> {
+        self.current_availability.as_ref()
+    }
 }
 
 /// A list of valid collection types.
@@ -128,7 +226,11 @@ pub enum Set {
     /// A curated set of menu items.
     #[serde(rename = "CuratedSet")]
     #[serde(alias = "PersonalizedCuratedSet")]
+    #[serde(alias = "TrendingSet")]
+    #[serde(alias = "BecauseYouSet")]
     Curated {
+        #[serde(rename = "setId")]
+        set_id: Uuid,
         items: Vec<Collection>,
         meta: Meta,
         text: Text,
@@ -143,6 +245,40 @@ pub enum Set {
 }
 
 impl Set {
+    /// Returns the unique ID of the set.
+    ///
+    /// For ref sets, this is the ID of the remote set which must be fetched.
+    #[inline]
+    pub fn id(&self) -> &Uuid {
+        match *self {
+            Set::Curated { ref set_id, .. } => set_id,
+            Set::Ref { ref ref_id, .. } => ref_id,
+        }
+    }
+
+    /// Returns the menu items contained in the current page of the set.
+    ///
+    /// Ref sets do not contain any items until they are fetched, so this always returns an empty
+    /// slice for them.
+    #[inline]
+    pub fn items(&self) -> &[Collection] {
+        match *self {
+            Set::Curated { ref items, .. } => items,
+            Set::Ref { .. } => &[],
+        }
+    }
+
+    /// Returns the pagination metadata for the set.
+    ///
+    /// Returns `Some` if this is a curated set or `None` otherwise.
+    #[inline]
+    pub fn meta(&self) -> Option<&Meta> {
+        match *self {
+            Set::Curated { ref meta, .. } => Some(meta),
+            Set::Ref { .. } => None,
+        }
+    }
+
     /// Returns the associated text data to be displayed, if any.
     #[inline]
     pub fn text(&self) -> &Text {
@@ -153,12 +289,41 @@ impl Set {
     }
 }
 
-/// Contains metadata for a curated set.
+/// Contains pagination metadata for a curated set.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Meta {
-    hits: u32,
-    offset: u16,
-    page_size: u32,
+    /// Total number of items in the set, across all pages.
+    pub hits: u32,
+    /// Index of the first item in the current page.
+    pub offset: u32,
+    /// Maximum number of items per page.
+    pub page_size: u32,
+}
+
+impl Meta {
+    /// Returns the offset of the page following the current one.
+    ///
+    /// Returns `None` if the current page is the last page of the set, or if the metadata is
+    /// malformed such that the offset would not increase.
+    #[inline]
+    pub fn next_offset(&self) -> Option<u32> {
+        if self.page_size == 0 || self.offset >= self.hits {
+            return None;
+        }
+
+        self.offset
+            .checked_add(self.page_size)
+            .filter(|&next| next < self.hits)
+    }
+
+    /// Builds the URL of the page beginning at item `offset`, given the URL of the set itself.
+    pub fn page_url(&self, set_url: &Url, offset: u32) -> Url {
+        let mut url = set_url.clone();
+        url.query_pairs_mut()
+            .append_pair("offset", &offset.to_string())
+            .append_pair("page_size", &self.page_size.to_string());
+        url
+    }
 }
 
 /// Contains background video art data.
@@ -200,4 +365,148 @@ mod tests {
         let v: RefSet = serde_json::from_str(json).expect("failed to parse \"ref\" set JSON");
         eprintln!("{}", serde_json::to_string_pretty(&v).unwrap());
     }
+
+    #[test]
+    fn exposes_content_metadata() {
+        let json = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/home.json"));
+        let home: Home = serde_json::from_str(json).expect("failed to parse `home.json`");
+        let containers = home.data["StandardCollection"].containers().unwrap();
+
+        let series = &containers[0].set.items()[0];
+        assert_eq!(series.kind(), CollectionKind::DmcSeries);
+        assert_eq!(
+            series.series_id().map(|id| id.to_string()).as_deref(),
+            Some("5ce23061-3c4a-40d8-9d08-179001be5a4f")
+        );
+        assert_eq!(series.ratings()[0].value, "TV-14");
+        assert_eq!(series.release_year(), Some(2020));
+        assert!(series.is_original());
+        let rights = series
+            .media_rights()
+            .expect("series must have media rights");
+        assert!(rights.download_blocked && !rights.pcon_blocked);
+
+        let collection = &containers[1].set.items()[0];
+        assert!(collection.ratings().is_empty());
+        assert_eq!(collection.release_year(), None);
+        assert!(!collection.is_original());
+        assert!(collection.media_rights().is_none());
+
+        let video = &containers[2].set.items()[0];
+        assert_eq!(video.series_id(), None);
+        assert_eq!(video.release_year(), Some(1993));
+        assert!(!video.is_original());
+    }
+
+    #[test]
+    fn round_trips_content_metadata() {
+        const FIELDS: &[&str] = &[
+            "contentId",
+            "currentAvailability",
+            "encodedSeriesId",
+            "mediaRights",
+            "ratings",
+            "releases",
+            "tags",
+            "textExperienceId",
+        ];
+
+        let fixtures = [
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/home.json")),
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/f506622c-4f75-4f87-bafe-3e08a4433914.json"
+            )),
+        ];
+
+        let mut num_items = 0;
+        for json in fixtures.iter() {
+            let value: serde_json::Value = serde_json::from_str(json).unwrap();
+            for item in find_content_items(&value) {
+                let collection: Collection = serde_json::from_value(item.clone()).unwrap();
+                let round_trip = serde_json::to_value(&collection).unwrap();
+                for field in FIELDS {
+                    assert_eq!(
+                        strip_nulls(&round_trip[field]),
+                        strip_nulls(&item[field]),
+                        "field {:?} did not round-trip for content {}",
+                        field,
+                        item["contentId"]
+                    );
+                }
+
+                num_items += 1;
+            }
+        }
+
+        assert!(num_items > 0, "fixtures must contain content items");
+    }
+
+    #[test]
+    fn computes_next_page() {
+        let mut meta = Meta {
+            hits: 48,
+            offset: 0,
+            page_size: 15,
+        };
+
+        let set_url: Url = "https://example.com/sets/1234.json".parse().unwrap();
+        let offset = meta
+            .next_offset()
+            .expect("first page must have a successor");
+        assert_eq!(offset, 15);
+        assert_eq!(
+            meta.page_url(&set_url, offset).as_str(),
+            "https://example.com/sets/1234.json?offset=15&page_size=15"
+        );
+
+        meta.offset = 45;
+        assert_eq!(meta.next_offset(), None);
+
+        // Malformed metadata must neither overflow nor loop back onto an earlier page.
+        meta.offset = u32::MAX - 1;
+        meta.hits = u32::MAX;
+        assert_eq!(meta.next_offset(), None);
+
+        meta.offset = 0;
+        meta.page_size = 0;
+        assert_eq!(meta.next_offset(), None);
+    }
+
+    /// Returns every series and video collection nested anywhere within `value`.
+    fn find_content_items(value: &serde_json::Value) -> Vec<&serde_json::Value> {
+        use serde_json::Value;
+
+        let mut items = Vec::new();
+        match value {
+            Value::Object(map) => {
+                if let Some("DmcSeries") | Some("DmcVideo") =
+                    map.get("type").and_then(Value::as_str)
+                {
+                    items.push(value);
+                }
+                items.extend(map.values().flat_map(find_content_items));
+            }
+            Value::Array(values) => items.extend(values.iter().flat_map(find_content_items)),
+            _ => {}
+        }
+
+        items
+    }
+
+    /// Recursively removes all `null` fields, since these are omitted when serializing.
+    fn strip_nulls(value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+
+        match value {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| (k.clone(), strip_nulls(v)))
+                    .collect(),
+            ),
+            Value::Array(values) => Value::Array(values.iter().map(strip_nulls).collect()),
+            _ => value.clone(),
+        }
+    }
 }
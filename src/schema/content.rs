@@ -0,0 +1,79 @@
+//! Types for deserializing content metadata, e.g. ratings and releases.
+
+use serde::{Deserialize, Serialize};
+
+/// A content rating assigned by a rating system, e.g. `TV-14` in the `TVPG` system.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Rating {
+    /// Name of the rating system, e.g. `TVPG` or `MPAA`.
+    pub system: String,
+    /// The rating itself, to be displayed in a badge.
+    pub value: String,
+    /// Content advisories accompanying the rating, e.g. `Violence`.
+    #[serde(default)]
+    pub advisories: Vec<String>,
+    /// Explanation of the rating, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A release of a piece of content.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    /// Date on which the content was released in `YYYY-MM-DD` format, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    /// Kind of the release, e.g. `original`.
+    pub release_type: String,
+    /// Year in which the content was released, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_year: Option<u16>,
+    /// Territory in which the content was released, or `None` if it was released worldwide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub territory: Option<String>,
+}
+
+/// A tag attached to a piece of content.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    /// Kind of the tag, e.g. `disneyPlusOriginal`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Value of the tag, e.g. `"true"`.
+    pub value: Option<String>,
+    /// Human-readable name of the tag, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Kind of entity the tag applies to, e.g. `series` or `program`, if specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+}
+
+/// Restrictions on how a piece of content may be consumed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaRights {
+    /// Indicates that the content cannot be downloaded for offline viewing.
+    pub download_blocked: bool,
+    /// Indicates that the content is blocked by parental controls.
+    pub pcon_blocked: bool,
+    /// Indicates whether the content may be rewound during playback, if specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewind: Option<bool>,
+    /// Rights violations preventing playback, if specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<String>>,
+}
+
+/// Where and to whom a piece of content is currently available.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Availability {
+    /// Region in which the content is available, e.g. `US`.
+    pub region: String,
+    /// Indicates whether the content is suitable for kids mode, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kids_mode: Option<bool>,
+}
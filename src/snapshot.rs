@@ -0,0 +1,292 @@
+//! Offline snapshots of the DSS API, for reproducing UI bugs without network access.
+//!
+//! A snapshot is a directory containing `home.json`, the ref set JSON files, and the images of the
+//! tiles and their details screens, along with a `manifest.json` file which maps every original URL
+//! to its local copy.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use fnv::{FnvHashMap as HashMap, FnvHasher};
+use futures_util::future::{FutureExt, LocalBoxFuture};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::fetcher::{
+    FetchError, Fetcher, FileTransport, Response, Transport, TransportResponse, TransportResult,
+};
+use crate::menu;
+use crate::schema::{self, Set};
+
+/// Name of the manifest file located in the root of every snapshot directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// How long to wait in between polling the pending downloads while recording.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Maps the URLs recorded in a snapshot to their local copies.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    /// URL of the `home.json` document from which the menu was recorded.
+    pub home: Option<Url>,
+    /// Paths of the recorded files, relative to the snapshot directory.
+    pub files: BTreeMap<Url, PathBuf>,
+}
+
+impl Manifest {
+    /// Loads the manifest of the snapshot located in `dir`.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILE_NAME);
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("could not read snapshot manifest {}", path.display()))?;
+        let manifest = serde_json::from_str(&json)?;
+        Ok(manifest)
+    }
+
+    /// Saves the manifest into the snapshot located in `dir`.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(dir.join(MANIFEST_FILE_NAME), json)?;
+        Ok(())
+    }
+}
+
+/// Serves files recorded in a snapshot directory in place of their original URLs.
+///
+/// URLs which are missing from the manifest result in `404 Not Found` responses.
+#[derive(Debug)]
+pub struct SnapshotTransport {
+    home: Option<Url>,
+    files: HashMap<Url, Url>,
+    inner: FileTransport,
+}
+
+impl SnapshotTransport {
+    /// Opens the snapshot located in `dir`.
+    pub fn open<P: AsRef<Path>>(dir: P) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let manifest = Manifest::load(dir)?;
+        let root = dir
+            .canonicalize()
+            .with_context(|| format!("could not open snapshot {}", dir.display()))?;
+
+        let files = manifest
+            .files
+            .into_iter()
+            .map(|(url, path)| {
+                let local_url = Url::from_file_path(root.join(&path))
+                    .map_err(|_| anyhow::anyhow!("invalid snapshot file {}", path.display()))?;
+                Ok((url, local_url))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(SnapshotTransport {
+            home: manifest.home,
+            files,
+            inner: FileTransport::new(),
+        })
+    }
+
+    /// Returns the URL of the `home.json` document from which the snapshot was recorded.
+    #[inline]
+    pub fn home_url(&self) -> Option<&Url> {
+        self.home.as_ref()
+    }
+}
+
+impl Transport for SnapshotTransport {
+    fn get(&self, url: &Url, headers: HeaderMap) -> LocalBoxFuture<'static, TransportResult> {
+        match self.files.get(url) {
+            Some(local_url) => self.inner.get(local_url, headers),
+            None => async { Ok(TransportResponse::empty(StatusCode::NOT_FOUND)) }.boxed_local(),
+        }
+    }
+}
+
+/// Records a snapshot of the menu located at `home_url` into `dir` using the given fetcher.
+///
+/// This downloads `home.json`, every ref set and page of each row, and all of the images displayed
+/// on the tiles and their details screens.
+/// Files which fail to download are left out of the snapshot, so they will fail to load in the
+/// same way when the snapshot is replayed. Returns the manifest of the recorded snapshot.
+pub fn record(fetcher: &Fetcher, home_url: &Url, dir: &Path) -> anyhow::Result<Manifest> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("could not create snapshot {}", dir.display()))?;
+
+    let mut manifest = Manifest {
+        home: Some(home_url.clone()),
+        files: BTreeMap::new(),
+    };
+
+    // Unlike the other files, the home menu is required for the snapshot to be useful at all.
+    // Documents are decoded on the background thread, and saved from the same copy in memory.
+    let home: schema::Home = fetcher.fetch_json(home_url.clone())?;
+    let home_bytes = fetcher.fetch_bytes(home_url.clone())?;
+    save_bytes(&mut manifest, dir, home_url, &home_bytes)?;
+
+    let mut sets = Vec::new();
+    let mut images = BTreeSet::new();
+    for row in menu::get_menu_rows(&home)? {
+        match row.set {
+            Set::Curated { .. } => visit_set(&row.set, &mut sets, &mut images),
+            Set::Ref { ref ref_id, .. } => sets.push(menu::get_ref_set_url(ref_id)?),
+        }
+    }
+
+    // Ref sets may link to further pages, so keep going until there are no more to fetch.
+    while let Some(url) = sets.pop() {
+        if manifest.files.contains_key(&url) {
+            continue;
+        }
+
+        if let Ok(bytes) = fetcher.fetch_bytes(url.clone()) {
+            if let Ok(ref_set) = fetcher.fetch_json::<schema::RefSet>(url.clone()) {
+                if let Ok(set) = menu::get_ref_set(&ref_set) {
+                    visit_set(set, &mut sets, &mut images);
+                }
+            }
+
+            save_bytes(&mut manifest, dir, &url, &bytes)?;
+        }
+    }
+
+    for (url, result) in fetch_all(fetcher, images) {
+        if let Ok(path) = result {
+            save_file(&mut manifest, dir, &url, &path)?;
+        }
+    }
+
+    manifest.save(dir)?;
+    Ok(manifest)
+}
+
+/// Collects the tile and details screen image URLs and the URL of the next page of the given set.
+fn visit_set(set: &Set, sets: &mut Vec<Url>, images: &mut BTreeSet<Url>) {
+    for item in set.items() {
+        images.extend(menu::get_tile_image_url(item).ok().cloned());
+        images.extend(menu::get_details_image_urls(item));
+    }
+
+    if let Ok(Some(next_page)) = menu::get_next_page(set) {
+        sets.push(next_page.url);
+    }
+}
+
+/// Downloads all of the given files concurrently, blocking until every download has finished.
+fn fetch_all<I>(fetcher: &Fetcher, urls: I) -> Vec<(Url, Result<PathBuf, FetchError>)>
+where
+    I: IntoIterator<Item = Url>,
+{
+    let mut pending: Vec<_> = urls.into_iter().collect();
+    let mut finished = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        pending.retain(|url| match fetcher.poll_fetch(url.clone()) {
+            Response::Pending(_) => true,
+            Response::Ready(result) => {
+                finished.push((url.clone(), result));
+                false
+            }
+        });
+
+        if !pending.is_empty() {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    finished
+}
+
+/// Copies a downloaded file into the snapshot directory and adds it to the manifest.
+fn save_file(manifest: &mut Manifest, dir: &Path, url: &Url, path: &Path) -> anyhow::Result<()> {
+    let file_name = get_file_name(url);
+    fs::copy(path, dir.join(&file_name))
+        .with_context(|| format!("could not save {} into snapshot", url))?;
+    manifest.files.insert(url.clone(), file_name);
+    Ok(())
+}
+
+/// Writes a file downloaded into memory into the snapshot directory and adds it to the manifest.
+fn save_bytes(manifest: &mut Manifest, dir: &Path, url: &Url, bytes: &[u8]) -> anyhow::Result<()> {
+    let file_name = get_file_name(url);
+    fs::write(dir.join(&file_name), bytes)
+        .with_context(|| format!("could not save {} into snapshot", url))?;
+    manifest.files.insert(url.clone(), file_name);
+    Ok(())
+}
+
+/// Returns the name of the local copy of `url` in the snapshot directory.
+///
+/// Files are named after a hash of their original URL, keeping the original file extension.
+fn get_file_name(url: &Url) -> PathBuf {
+    let mut hasher = FnvHasher::default();
+    hasher.write(url.as_str().as_bytes());
+
+    let mut file_name = PathBuf::from(format!("{:016x}", hasher.finish()));
+    if let Some(extension) = Path::new(url.path()).extension() {
+        file_name.set_extension(extension);
+    }
+
+    file_name
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fetcher::{self, FetcherConfig, MemoryTransport};
+
+    use super::*;
+
+    const HOME_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/home.json"));
+    const REF_SET_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/f506622c-4f75-4f87-bafe-3e08a4433914.json"
+    ));
+    const REF_SET_ID: &str = "f506622c-4f75-4f87-bafe-3e08a4433914";
+
+    #[test]
+    fn records_and_replays_snapshot() {
+        let home_url: Url = menu::HOME_JSON_URL.parse().unwrap();
+        let ref_set_url = menu::get_ref_set_url(&REF_SET_ID.parse().unwrap()).unwrap();
+
+        let live = MemoryTransport::new();
+        live.insert(home_url.clone(), HOME_JSON);
+        live.insert(ref_set_url.clone(), REF_SET_JSON);
+
+        let home: schema::Home = serde_json::from_str(HOME_JSON).unwrap();
+        let rows = menu::get_menu_rows(&home).unwrap();
+        let details_urls = menu::get_details_image_urls(&rows[0].set.items()[0]);
+        assert!(!details_urls.is_empty());
+        for url in &details_urls {
+            live.insert(url.clone(), "image");
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let fetcher = fetcher::spawn(FetcherConfig::new().with_transport(live)).unwrap();
+        let manifest = record(&fetcher, &home_url, dir.path()).expect("failed to record snapshot");
+        assert_eq!(manifest.home.as_ref(), Some(&home_url));
+        assert!(manifest.files.contains_key(&ref_set_url));
+        assert!(details_urls
+            .iter()
+            .all(|url| manifest.files.contains_key(url)));
+
+        let snapshot = SnapshotTransport::open(dir.path()).expect("failed to open snapshot");
+        assert_eq!(snapshot.home_url(), Some(&home_url));
+
+        let fetcher = fetcher::spawn(FetcherConfig::new().with_transport(snapshot)).unwrap();
+        let path = fetcher
+            .fetch(ref_set_url)
+            .expect("failed to replay ref set");
+        assert_eq!(fs::read_to_string(path).unwrap(), REF_SET_JSON);
+
+        let error = fetcher
+            .fetch("https://example.com/missing.json".parse().unwrap())
+            .expect_err("file missing from snapshot was served");
+        assert!(error.is_not_found());
+    }
+}